@@ -8,5 +8,8 @@ module.exports = async function (provider: anchor.AnchorProvider) {
   // Configure client to use the provider.
   anchor.setProvider(provider);
 
-  // Add your deploy script here.
+  // Accounts of the first release (8Q7GV6Hw2x1FRvV1TUG5LQvUea82HuS2HK297q7LzWDv) are
+  // not migrated. This program is deployed fresh under the program id in Anchor.toml,
+  // from target/deploy/linear_staking-keypair.json, and its vaults are initialized
+  // with `initialize`.
 };
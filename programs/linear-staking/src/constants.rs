@@ -1,19 +1,50 @@
 use anchor_lang::prelude::*;
 
+/// Stake vault PDA: [STAKE_VAULT_SEED, token_mint]
 #[constant]
 pub const STAKE_VAULT_SEED: &[u8] = b"stake_vault";
 
+/// Vault token account PDA: [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault]
 #[constant]
 pub const STAKE_VAULT_TOKEN_ACCOUNT_SEED: &[u8] = b"stake_vault_token_account";
 
+/// User stake PDA: [USER_STAKE_SEED, stake_vault, owner]
 #[constant]
 pub const USER_STAKE_SEED: &[u8] = b"user_stake";
 
+/// Reward token account PDA: [REWARD_TOKEN_ACCOUNT_SEED, stake_vault, reward_mint]
+#[constant]
+pub const REWARD_TOKEN_ACCOUNT_SEED: &[u8] = b"reward_token_account";
+
+/// Unstake request PDA: [UNSTAKE_REQUEST_SEED, user_stake, generation (le bytes), request_id (le bytes)]
+#[constant]
+pub const UNSTAKE_REQUEST_SEED: &[u8] = b"unstake_request";
+
+/// Voting power record PDA: [VOTING_POWER_SEED, user_stake]
+#[constant]
+pub const VOTING_POWER_SEED: &[u8] = b"voting_power";
+
+/// Liquid staking receipt mint PDA: [RECEIPT_MINT_SEED, stake_vault]
+#[constant]
+pub const RECEIPT_MINT_SEED: &[u8] = b"receipt_mint";
+
+/// Transfer authority PDA: [TRANSFER_AUTHORITY_SEED, stake_vault]
 #[constant]
 pub const TRANSFER_AUTHORITY_SEED: &[u8] = b"transfer_authority";
 
 /// Default vesting period: 30 days in seconds
-pub const DEFAULT_VESTING_PERIOD: i64 = 30 * 24 * 60 * 60; // 2,592,000 seconds
+pub const DEFAULT_VESTING_PERIOD: u64 = 30 * 24 * 60 * 60; // 2,592,000 seconds
+
+/// Basis points denominator for rates expressed in bps
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Reward weight multipliers for lockup tiers, in bps (10_000 = 1x)
+pub const THREE_MONTH_LOCKUP_MULTIPLIER_BPS: u16 = 12_500;
+pub const SIX_MONTH_LOCKUP_MULTIPLIER_BPS: u16 = 15_000;
+pub const TWELVE_MONTH_LOCKUP_MULTIPLIER_BPS: u16 = 20_000;
+
+/// Period over which early-exit penalties are streamed to stakers when no reward period is running: 7 days
+pub const PENALTY_REWARD_DURATION: u64 = 7 * 24 * 60 * 60;
 
 /// Token decimals (adjust based on your token)
 pub const TOKEN_DECIMALS: u8 = 9;
@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
 
+/// Codes follow declaration order: new variants are appended, and variants no longer
+/// returned stay in place so released codes keep their meaning.
 #[error_code]
 pub enum ErrorCode {
     #[msg("Deposits are currently disabled")]
@@ -61,7 +63,7 @@ pub enum ErrorCode {
     #[msg("Vault is not paused")]
     NotPaused,
 
-    #[msg("Vault must be paused for emergency withdraw")]
+    #[msg("Vault must be paused to enter emergency mode")]
     VaultNotPaused,
 
     #[msg("Invalid vesting period")]
@@ -69,4 +71,76 @@ pub enum ErrorCode {
 
     #[msg("Insufficient vault balance")]
     InsufficientVaultBalance,
+
+    #[msg("Reward token account does not belong to a reward stream of this vault")]
+    InvalidRewardStream,
+
+    #[msg("Maximum number of reward streams reached")]
+    MaxRewardStreamsReached,
+
+    #[msg("Invalid reward token account")]
+    InvalidRewardTokenAccount,
+
+    #[msg("Invalid reward duration")]
+    InvalidRewardDuration,
+
+    #[msg("Cliff period cannot exceed the vesting period")]
+    InvalidCliffPeriod,
+
+    #[msg("Invalid vesting schedule")]
+    InvalidVestingSchedule,
+
+    #[msg("Invalid early-exit penalty rate")]
+    InvalidPenaltyRate,
+
+    #[msg("Invalid penalty destination account")]
+    InvalidPenaltyDestination,
+
+    #[msg("Maximum number of stake lockups reached")]
+    MaxStakeLocksReached,
+
+    #[msg("Amount is still locked")]
+    StakeLocked,
+
+    #[msg("Vault is not in emergency mode")]
+    EmergencyModeNotActive,
+
+    #[msg("Vault is in emergency mode")]
+    EmergencyModeActive,
+
+    #[msg("Token account is not owned by the vault")]
+    InvalidTokenAccount,
+
+    #[msg("User stake still holds stake or unstake requests")]
+    UserStakeNotEmpty,
+
+    #[msg("Collect outstanding rewards before closing the user stake")]
+    UnclaimedRewardsRemaining,
+
+    #[msg("Every open unstake request must be passed")]
+    MissingUnstakeRequests,
+
+    #[msg("Only linear unstake requests past their cliff can be merged")]
+    UnstakeRequestsNotMergeable,
+
+    #[msg("Lockups are not available for liquid stake")]
+    LockupNotAllowed,
+
+    #[msg("Receipt mint, receipt token account and transfer authority must be passed together")]
+    MissingReceiptAccounts,
+
+    #[msg("Position cannot be transferred to its owner")]
+    InvalidTransferRecipient,
+
+    #[msg("Owner cannot be their own delegate")]
+    InvalidDelegate,
+
+    #[msg("Rewards compounded into the liquid pool must be settled first")]
+    LiquidRewardsNotSettled,
+
+    #[msg("Voting power history is full until governance moves its retention slot forward")]
+    VotingPowerHistoryFull,
+
+    #[msg("No expired lockups to drop")]
+    NoExpiredStakeLocks,
 }
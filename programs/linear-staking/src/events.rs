@@ -1,10 +1,15 @@
 use anchor_lang::prelude::*;
 
+use crate::state::VestingSchedule;
+
 #[event]
 pub struct VaultInitialized {
     pub admin: Pubkey,
     pub token_mint: Pubkey,
+    pub reward_mint: Pubkey,
     pub vesting_period_seconds: u64,
+    pub cliff_period_seconds: u64,
+    pub vesting_schedule: VestingSchedule,
     pub timestamp: i64,
 }
 
@@ -14,6 +19,37 @@ pub struct StakeDeposited {
     pub amount: u64,
     pub total_staked: u64,
     pub active_stake_amount: u64,
+    pub weighted_stake_amount: u64,
+    pub unlock_time: Option<i64>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StakeDepositedFor {
+    pub payer: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+    pub active_stake_amount: u64,
+    pub weighted_stake_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidStakeDeposited {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub receipt_amount: u64,
+    pub pooled_amount: u64,
+    pub receipt_supply: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidRewardsSettled {
+    pub stake_vault: Pubkey,
+    pub amount: u64,
+    pub pooled_amount: u64,
     pub timestamp: i64,
 }
 
@@ -21,9 +57,11 @@ pub struct StakeDeposited {
 pub struct UnstakeRequested {
     pub user: Pubkey,
     pub amount: u64,
-    pub request_index: u64,
+    pub request_id: u64,
     pub start_time: i64,
+    pub cliff_time: i64,
     pub end_time: i64,
+    pub vesting_schedule: VestingSchedule,
 }
 
 #[event]
@@ -31,38 +69,158 @@ pub struct VestedTokensClaimed {
     pub user: Pubkey,
     pub amount: u64,
     pub remaining_unstaking: u64,
+    pub claims: Vec<RequestClaim>,
     pub timestamp: i64,
 }
 
+/// Amount claimed from a single unstake request
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RequestClaim {
+    pub request_id: u64,
+    pub amount: u64,
+}
+
 #[event]
 pub struct UnstakeCancelled {
     pub user: Pubkey,
-    pub request_index: u8,
+    pub request_id: u64,
     pub amount_returned: u64,
+    pub remaining_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UnstakeRequestsMerged {
+    pub user: Pubkey,
+    pub request_id: u64,
+    pub merged_request_ids: Vec<u64>,
+    pub total_amount: u64,
+    pub start_time: i64,
+    pub end_time: i64,
     pub timestamp: i64,
 }
 
 #[event]
 pub struct RewardsDeposited {
     pub admin: Pubkey,
+    pub reward_mint: Pubkey,
     pub amount: u64,
     pub total_pending: u128,
+    pub reward_rate: u128,
+    pub period_finish: i64,
     pub timestamp: i64,
 }
 
 #[event]
 pub struct RewardsDistributed {
     pub distributor: Pubkey,
+    pub reward_mint: Pubkey,
     pub amount: u128,
     pub reward_per_token: u128,
-    pub total_active_stake: u64,
+    pub total_weighted_stake: u64,
     pub timestamp: i64,
 }
 
 #[event]
 pub struct RewardsCollected {
     pub user: Pubkey,
+    pub reward_mint: Pubkey,
     pub amount: u64,
     pub total_claimed: u64,
     pub timestamp: i64,
-}
\ No newline at end of file
+}
+
+#[event]
+pub struct RewardsCompounded {
+    pub user: Pubkey,
+    pub caller: Pubkey,
+    pub amount: u64,
+    pub active_stake_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InstantUnstaked {
+    pub user: Pubkey,
+    pub request_id: u64,
+    pub amount_released: u64,
+    pub penalty: u64,
+    pub penalty_destination: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VotingPowerCheckpointed {
+    pub user: Pubkey,
+    pub stake_vault: Pubkey,
+    pub slot: u64,
+    pub voting_power: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StakeLocksExpired {
+    pub user: Pubkey,
+    pub stake_vault: Pubkey,
+    pub weighted_stake_amount: u64,
+    pub total_weighted_stake: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyExited {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SurplusSwept {
+    pub admin: Pubkey,
+    pub token_account: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub obligation: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PositionTransferred {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub staked_amount: u64,
+    pub unstake_requests: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserStakeClosed {
+    pub user: Pubkey,
+    pub stake_vault: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AutoCompoundUpdated {
+    pub user: Pubkey,
+    pub stake_vault: Pubkey,
+    pub enabled: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DelegateUpdated {
+    pub user: Pubkey,
+    pub stake_vault: Pubkey,
+    pub delegate: Option<Pubkey>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidStakeEmergencyRedeemed {
+    pub user: Pubkey,
+    pub receipt_amount: u64,
+    pub amount: u64,
+    pub pooled_amount: u64,
+    pub timestamp: i64,
+}
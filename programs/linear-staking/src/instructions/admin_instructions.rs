@@ -1,24 +1,31 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
 
 use crate::{
-    constants::{STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED, EVENT_AUTHORITY_SEED},
+    constants::{
+        BPS_DENOMINATOR, STAKE_VAULT_SEED, RECEIPT_MINT_SEED, REWARD_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED,
+        EVENT_AUTHORITY_SEED,
+    },
     error::ErrorCode,
+    events::SurplusSwept,
     program::LinearStaking,
+    state::stake_vault::MAX_REWARD_STREAMS,
+    RewardState,
     StakeVault,
+    VestingSchedule,
 };
 
 #[derive(Accounts)]
 pub struct PauseVault<'info> {
     #[account(
         mut,
-        constraint = admin.key() == stake_vault.admin @ ErrorCode::Unauthorized
+        constraint = admin.key() == stake_vault.roles.pauser @ ErrorCode::Unauthorized
     )]
     pub admin: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump = stake_vault.bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
@@ -41,13 +48,13 @@ pub fn pause_handler(ctx: Context<PauseVault>) -> Result<()> {
 pub struct UnpauseVault<'info> {
     #[account(
         mut,
-        constraint = admin.key() == stake_vault.admin @ ErrorCode::Unauthorized
+        constraint = admin.key() == stake_vault.roles.pauser @ ErrorCode::Unauthorized
     )]
     pub admin: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump = stake_vault.bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
@@ -59,6 +66,7 @@ pub fn unpause_handler(ctx: Context<UnpauseVault>) -> Result<()> {
     require!(
         stake_vault.is_paused, ErrorCode::NotPaused
     );
+    require!(!stake_vault.is_emergency, ErrorCode::EmergencyModeActive);
 
     stake_vault.is_paused = false;                                                                                                                                                                                                                                                                                                                                                                                                 
     msg!("Vault unpaused");                                                                                                                                                                                        
@@ -69,13 +77,13 @@ pub fn unpause_handler(ctx: Context<UnpauseVault>) -> Result<()> {
 pub struct UpdateVestingPeriod<'info> {
     #[account(
         mut,
-        constraint = admin.key() == stake_vault.admin @ ErrorCode::Unauthorized
+        constraint = admin.key() == stake_vault.roles.config_manager @ ErrorCode::Unauthorized
     )]
     pub admin: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump = stake_vault.bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
@@ -93,9 +101,14 @@ pub fn update_vesting_period_handler(
     let stake_vault = &mut ctx.accounts.stake_vault;
 
     require!(params.new_vesting_period_seconds > 0, ErrorCode::InvalidVestingPeriod);
+    require!(
+        params.new_vesting_period_seconds >= stake_vault.cliff_period_seconds,
+        ErrorCode::InvalidCliffPeriod
+    );
 
     stake_vault.vesting_period_seconds = params.new_vesting_period_seconds;
 
+    // Outstanding unstake requests keep the period they were created with
     msg!(
         "Vesting period updated to {} seconds",
         stake_vault.vesting_period_seconds
@@ -103,95 +116,336 @@ pub fn update_vesting_period_handler(
     Ok(())
 }
 
-#[derive(Accounts)]                                                                                                                                                                                                
-pub struct EmergencyWithdrawCtx<'info> {                                                                                                                                                                           
-    #[account(                                                                                                                                                                                                     
-        mut,                                                                                                                                                                                                       
-        constraint = admin.key() == stake_vault.admin @ ErrorCode::Unauthorized                                                                                                                                    
-    )]                                                                                                                                                                                                             
-    pub admin: Signer<'info>,                                                                                                                                                                                      
-                                                                                                                                                                                                                    
-    #[account(                                                                                                                                                                                                     
-        mut,                                                                                                                                                                                                       
-        seeds = [STAKE_VAULT_SEED],                                                                                                                                                                                
-        bump = stake_vault.bump                                                                                                                                                                                    
-    )]                                                                                                                                                                                                             
-    pub stake_vault: Account<'info, StakeVault>,                                                                                                                                                                   
-                                                                                                                                                                                                                    
-    #[account(                                                                                                                                                                                                     
-        mut,                                                                                                                                                                                                       
-        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED],                                                                                                                                                                  
-        bump = stake_vault.token_account_bump                                                                                                                                                                      
-    )]                                                                                                                                                                                                             
-    pub vault_token_account: Account<'info, TokenAccount>,                                                                                                                                                         
-                                                                                                                                                                                                                    
-    #[account(                                                                                                                                                                                                     
-        mut,                                                                                                                                                                                                       
-        constraint = admin_token_account.owner == admin.key() @ ErrorCode::Unauthorized                                                                                                                            
-    )]                                                                                                                                                                                                             
-    pub admin_token_account: Account<'info, TokenAccount>,                                                                                                                                                         
-                                                                                                                                                                                                                    
-    /// CHECK: PDA authority for token transfers                                                                                                                                                                   
-    #[account(                                                                                                                                                                                                     
-        seeds = [TRANSFER_AUTHORITY_SEED],                                                                                                                                                                         
-        bump = stake_vault.transfer_authority_bump                                                                                                                                                                 
-    )]                                                                                                                                                                                                             
-    pub transfer_authority: AccountInfo<'info>,                                                                                                                                                                    
-                                                                                                                                                                                                                    
-    pub token_program: Program<'info, Token>,                                                                                                                                                                      
-                                                                                                                                                                                                                    
-    /// CHECK: event authority for emit_cpi                                                                                                                                                                        
-    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]                                                                                                                                                               
-    pub event_authority: AccountInfo<'info>,                                                                                                                                                                       
-                                                                                                                                                                                                                    
-    pub program: Program<'info, LinearStaking>,                                                                                                                                                                    
-}                                                                                                                                                                                                                  
-                                                                                                                                                                                                                    
-#[derive(AnchorSerialize, AnchorDeserialize)]                                                                                                                                                                      
-pub struct EmergencyWithdrawParams {                                                                                                                                                                               
-    pub amount: u64,                                                                                                                                                                                               
-}     
+#[derive(Accounts)]
+pub struct UpdateCliffPeriod<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == stake_vault.roles.config_manager @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+}
 
-pub fn emergency_withdraw_handler(
-    ctx: Context<EmergencyWithdrawCtx>,
-    params: EmergencyWithdrawParams,
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateCliffPeriodParams {
+    pub new_cliff_period_seconds: u64,
+}
+
+pub fn update_cliff_period_handler(
+    ctx: Context<UpdateCliffPeriod>,
+    params: UpdateCliffPeriodParams,
+) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+
+    require!(
+        params.new_cliff_period_seconds <= stake_vault.vesting_period_seconds,
+        ErrorCode::InvalidCliffPeriod
+    );
+
+    stake_vault.cliff_period_seconds = params.new_cliff_period_seconds;
+
+    // Outstanding unstake requests keep the cliff they were created with
+    msg!(
+        "Cliff period updated to {} seconds",
+        stake_vault.cliff_period_seconds
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateVestingSchedule<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == stake_vault.roles.config_manager @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateVestingScheduleParams {
+    pub new_vesting_schedule: VestingSchedule,
+}
+
+pub fn update_vesting_schedule_handler(
+    ctx: Context<UpdateVestingSchedule>,
+    params: UpdateVestingScheduleParams,
 ) -> Result<()> {
     let stake_vault = &mut ctx.accounts.stake_vault;
 
-    // Must be paused for emergency withdraw
+    params.new_vesting_schedule.validate()?;
+
+    stake_vault.vesting_schedule = params.new_vesting_schedule;
+
+    // Outstanding unstake requests keep the curve they were created with
+    msg!("Vesting schedule updated to {:?}", stake_vault.vesting_schedule);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateEarlyExitConfig<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == stake_vault.roles.config_manager @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateEarlyExitConfigParams {
+    pub penalty_bps: Option<u16>,
+    /// Pubkey::default() redistributes penalties to stakers, which needs a reward stream paid in the staked mint
+    pub treasury: Option<Pubkey>,
+}
+
+pub fn update_early_exit_config_handler(
+    ctx: Context<UpdateEarlyExitConfig>,
+    params: UpdateEarlyExitConfigParams,
+) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+
+    if let Some(penalty_bps) = params.penalty_bps {
+        require!(
+            penalty_bps as u64 <= BPS_DENOMINATOR,
+            ErrorCode::InvalidPenaltyRate
+        );
+        stake_vault.early_exit.penalty_bps = penalty_bps;
+        msg!("Early-exit penalty set to: {} bps", penalty_bps);
+    }
+
+    if let Some(treasury) = params.treasury {
+        stake_vault.early_exit.treasury = treasury;
+        msg!("Early-exit treasury set to: {}", treasury);
+    }
+
+    // Redistributed penalties need a reward stream paid in the staked mint to go to
+    let early_exit = &stake_vault.early_exit;
+    if early_exit.penalty_bps > 0 && early_exit.treasury == Pubkey::default() {
+        require!(
+            stake_vault
+                .reward_streams
+                .iter()
+                .any(|stream| stream.mint == stake_vault.token_mint),
+            ErrorCode::InvalidPenaltyDestination
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateVotingPowerRetention<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == stake_vault.roles.config_manager @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateVotingPowerRetentionParams {
+    /// Earliest slot governance still reads voting power at, None when no history is needed
+    pub retention_slot: Option<u64>,
+}
+
+pub fn update_voting_power_retention_handler(
+    ctx: Context<UpdateVotingPowerRetention>,
+    params: UpdateVotingPowerRetentionParams,
+) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+
+    stake_vault.voting_power_retention_slot = params.retention_slot;
+
+    // Full histories merge their newest checkpoints while older ones are still needed
+    msg!("Voting power retention slot updated to {:?}", stake_vault.voting_power_retention_slot);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct EnableEmergencyMode<'info> {
+    #[account(
+        constraint = admin.key() == stake_vault.roles.emergency_guardian @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+}
+
+pub fn enable_emergency_mode_handler(ctx: Context<EnableEmergencyMode>) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let current_time = Clock::get()?.unix_timestamp;
+
     require!(stake_vault.is_paused, ErrorCode::VaultNotPaused);
+    require!(!stake_vault.is_emergency, ErrorCode::EmergencyModeActive);
+
+    // Rewards streamed so far stay owed to users, the rest becomes surplus
+    stake_vault.update_reward_streams(current_time)?;
+    for reward_stream in stake_vault.reward_streams.iter_mut() {
+        reward_stream.halt(current_time);
+    }
+
+    stake_vault.is_emergency = true;
+
+    msg!("Emergency mode enabled, users can exit with their full stake");
+    Ok(())
+}
+
+/// Shared by `emergency_withdraw` and `sweep_surplus`, which only ever move surplus
+#[derive(Accounts)]
+pub struct WithdrawSurplus<'info> {
+    #[account(
+        constraint = admin.key() == stake_vault.roles.emergency_guardian @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    /// Vault token account, a reward stream's token account or any other account owned by
+    /// the transfer authority, e.g. an ATA that received tokens of another mint by mistake
+    #[account(
+        mut,
+        constraint = source_token_account.owner == transfer_authority.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub source_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = admin_token_account.owner == admin.key() @ ErrorCode::Unauthorized
+    )]
+    pub admin_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = source_token_account.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA authority for token transfers
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct WithdrawSurplusParams {
+    /// 0 withdraws the whole surplus
+    pub amount: u64,
+}
+
+/// Transfer surplus of the source token account to the guardian. Staked principal,
+/// unclaimed and not yet streamed rewards stay in the vault.
+/// Returns the amount withdrawn and the obligation kept back.
+fn withdraw_surplus(accounts: &WithdrawSurplus, amount: u64) -> Result<(u64, u64)> {
+    let stake_vault = &accounts.stake_vault;
+    let source_token_account = &accounts.source_token_account;
 
-    let vault_balance = ctx.accounts.vault_token_account.amount;
-    let withdraw_amount = if params.amount == 0 {
-        vault_balance
+    let obligation = stake_vault.token_account_obligation(&source_token_account.key())?;
+    let surplus = source_token_account.amount.saturating_sub(obligation);
+
+    let withdraw_amount = if amount == 0 {
+        surplus
     } else {
-        params.amount
+        amount
     };
 
     require!(withdraw_amount > 0, ErrorCode::InvalidAmount);
-    require!(withdraw_amount <= vault_balance, ErrorCode::InsufficientVaultBalance);
-
-    // Transfer tokens from vault to admin
-    let seeds = &[TRANSFER_AUTHORITY_SEED, &[stake_vault.transfer_authority_bump]];
+    require!(withdraw_amount <= surplus, ErrorCode::InsufficientVaultBalance);
+
+    let stake_vault_key = stake_vault.key();
+    let seeds = &[
+        TRANSFER_AUTHORITY_SEED,
+        stake_vault_key.as_ref(),
+        &[stake_vault.transfer_authority_bump],
+    ];
     let signer_seeds = &[&seeds[..]];
 
-    token::transfer(
+    token_interface::transfer_checked(
         CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.vault_token_account.to_account_info(),
-                to: ctx.accounts.admin_token_account.to_account_info(),
-                authority: ctx.accounts.transfer_authority.to_account_info(),
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: source_token_account.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                to: accounts.admin_token_account.to_account_info(),
+                authority: accounts.transfer_authority.to_account_info(),
             },
             signer_seeds,
         ),
         withdraw_amount,
+        accounts.mint.decimals,
     )?;
 
+    Ok((withdraw_amount, obligation))
+}
+
+pub fn emergency_withdraw_handler(
+    ctx: Context<WithdrawSurplus>,
+    params: WithdrawSurplusParams,
+) -> Result<()> {
+    require!(ctx.accounts.stake_vault.is_emergency, ErrorCode::EmergencyModeNotActive);
+
+    let (withdraw_amount, _) = withdraw_surplus(ctx.accounts, params.amount)?;
+
     msg!("Emergency withdraw: {} tokens", withdraw_amount);
     Ok(())
 }
 
+pub fn sweep_surplus_handler(ctx: Context<WithdrawSurplus>, params: WithdrawSurplusParams) -> Result<()> {
+    let (sweep_amount, obligation) = withdraw_surplus(ctx.accounts, params.amount)?;
+
+    emit_cpi!(SurplusSwept {
+        admin: ctx.accounts.admin.key(),
+        token_account: ctx.accounts.source_token_account.key(),
+        mint: ctx.accounts.source_token_account.mint,
+        amount: sweep_amount,
+        obligation,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
 // ========================================================================
 // Permission Management
 // ========================================================================
@@ -199,13 +453,13 @@ pub fn emergency_withdraw_handler(
 #[derive(Accounts)]
 pub struct UpdatePermissions<'info> {
     #[account(
-        constraint = admin.key() == stake_vault.admin @ ErrorCode::Unauthorized
+        constraint = admin.key() == stake_vault.roles.config_manager @ ErrorCode::Unauthorized
     )]
     pub admin: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump = stake_vault.bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
@@ -234,4 +488,239 @@ pub fn update_permissions_handler(
     }
 
     Ok(())
-}         
\ No newline at end of file
+}         
+
+// ========================================================================
+// Reward Streams
+// ========================================================================
+
+#[derive(Accounts)]
+pub struct AddRewardStream<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == stake_vault.roles.config_manager @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = admin,
+        token::mint = reward_mint,
+        token::authority = transfer_authority,
+        seeds = [REWARD_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref(), reward_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA authority for token transfers
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn add_reward_stream_handler(ctx: Context<AddRewardStream>) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+
+    require!(
+        stake_vault.reward_streams.len() < MAX_REWARD_STREAMS,
+        ErrorCode::MaxRewardStreamsReached
+    );
+
+    stake_vault.reward_streams.push(RewardState {
+        mint: ctx.accounts.reward_mint.key(),
+        token_account: ctx.accounts.reward_token_account.key(),
+        token_account_bump: ctx.bumps.reward_token_account,
+        ..RewardState::default()
+    });
+
+    msg!(
+        "Reward stream {} added for mint {}",
+        stake_vault.reward_streams.len() - 1,
+        ctx.accounts.reward_mint.key()
+    );
+    Ok(())
+}
+
+// ========================================================================
+// Liquid Staking
+// ========================================================================
+
+#[derive(Accounts)]
+pub struct EnableLiquidStaking<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == stake_vault.roles.config_manager @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = token_mint.decimals,
+        mint::authority = transfer_authority,
+        mint::token_program = token_program,
+        seeds = [RECEIPT_MINT_SEED, stake_vault.key().as_ref()],
+        bump
+    )]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA authority for token transfers, mints receipts
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn enable_liquid_staking_handler(ctx: Context<EnableLiquidStaking>) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+
+    stake_vault.liquid_pool.receipt_mint = ctx.accounts.receipt_mint.key();
+    stake_vault.liquid_pool.receipt_mint_bump = ctx.bumps.receipt_mint;
+
+    msg!(
+        "Liquid staking enabled with receipt mint {}",
+        ctx.accounts.receipt_mint.key()
+    );
+    Ok(())
+}
+
+// ========================================================================
+// Admin & Role Management
+// ========================================================================
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    #[account(
+        constraint = admin.key() == stake_vault.admin @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ProposeAdminParams {
+    /// Pubkey::default() cancels a pending proposal
+    pub new_admin: Pubkey,
+}
+
+pub fn propose_admin_handler(ctx: Context<ProposeAdmin>, params: ProposeAdminParams) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+
+    stake_vault.pending_admin = params.new_admin;
+
+    msg!("Proposed new admin {}", stake_vault.pending_admin);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        constraint = new_admin.key() == stake_vault.pending_admin @ ErrorCode::Unauthorized
+    )]
+    pub new_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+}
+
+pub fn accept_admin_handler(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+
+    stake_vault.admin = ctx.accounts.new_admin.key();
+    stake_vault.pending_admin = Pubkey::default();
+
+    msg!("Admin transferred to {}", stake_vault.admin);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateRoles<'info> {
+    #[account(
+        constraint = admin.key() == stake_vault.admin @ ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateRolesParams {
+    pub pauser: Option<Pubkey>,
+    pub reward_depositor: Option<Pubkey>,
+    pub config_manager: Option<Pubkey>,
+    pub emergency_guardian: Option<Pubkey>,
+}
+
+pub fn update_roles_handler(ctx: Context<UpdateRoles>, params: UpdateRolesParams) -> Result<()> {
+    let roles = &mut ctx.accounts.stake_vault.roles;
+
+    if let Some(pauser) = params.pauser {
+        roles.pauser = pauser;
+    }
+
+    if let Some(reward_depositor) = params.reward_depositor {
+        roles.reward_depositor = reward_depositor;
+    }
+
+    if let Some(config_manager) = params.config_manager {
+        roles.config_manager = config_manager;
+    }
+
+    if let Some(emergency_guardian) = params.emergency_guardian {
+        roles.emergency_guardian = emergency_guardian;
+    }
+
+    msg!(
+        "Roles updated: pauser {}, reward depositor {}, config manager {}, emergency guardian {}",
+        roles.pauser,
+        roles.reward_depositor,
+        roles.config_manager,
+        roles.emergency_guardian
+    );
+    Ok(())
+}
@@ -1,33 +1,62 @@
 use anchor_lang::prelude::*;
 
 use crate::{
-    constants::{STAKE_VAULT_SEED, USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
+    constants::{STAKE_VAULT_SEED, UNSTAKE_REQUEST_SEED, USER_STAKE_SEED, VOTING_POWER_SEED, EVENT_AUTHORITY_SEED},
     error::ErrorCode,
     events::UnstakeCancelled,
-    state::{StakeVault, UserStake},
-    instructions::helpers::{refresh_user_rewards, update_reward_snapshot_after_stake_change},
+    state::{StakeVault, UnstakeRequest, UserStake, VotingPowerRecord},
+    instructions::helpers::{
+        record_voting_power, refresh_user_rewards, settle_expired_stake_locks,
+        update_reward_snapshot_after_stake_change,
+    },
     program::LinearStaking,
 };
 
 #[derive(Accounts)]
+#[instruction(params: CancelUnstakeParams)]
 pub struct CancelUnstake<'info> {
+    /// Receives the request's rent once it is fully cancelled
+    #[account(mut)]
     pub owner: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump = stake_vault.bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
 
     #[account(
         mut,
-        seeds = [USER_STAKE_SEED, owner.key().as_ref()],
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
         bump = user_stake.bump,
         constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
     )]
     pub user_stake: Account<'info, UserStake>,
 
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    #[account(
+        mut,
+        seeds = [
+            UNSTAKE_REQUEST_SEED,
+            user_stake.key().as_ref(),
+            user_stake.generation.to_le_bytes().as_ref(),
+            params.request_id.to_le_bytes().as_ref()
+        ],
+        bump = unstake_request.bump
+    )]
+    pub unstake_request: Account<'info, UnstakeRequest>,
+
+    pub system_program: Program<'info, System>,
+
     /// CHECK: event authority for emit_cpi
     #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
     pub event_authority: AccountInfo<'info>,
@@ -37,71 +66,95 @@ pub struct CancelUnstake<'info> {
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct CancelUnstakeParams {
-    pub request_index: u8,
+    pub request_id: u64,
+    /// Amount to move back to active stake, the whole unclaimed remainder when None
+    pub amount: Option<u64>,
 }
 
 pub fn handler(ctx: Context<CancelUnstake>, params: CancelUnstakeParams) -> Result<()> {
     let stake_vault = &mut ctx.accounts.stake_vault;
     let user_stake = &mut ctx.accounts.user_stake;
     let current_time = Clock::get()?.unix_timestamp;
-    let request_index = params.request_index as usize;
-
-    // Validate request index
-    require!(
-        request_index < user_stake.unstake_requests.len(),
-        ErrorCode::InvalidRequestIndex
-    );
 
     require!(
         !stake_vault.is_paused,
         ErrorCode::VaultPaused
     );
 
-    // Refresh user rewards before changing stake
+    // Stream rewards up to now, then refresh user rewards before changing stake
+    settle_expired_stake_locks(user_stake, stake_vault, current_time)?;
+    stake_vault.update_reward_streams(current_time)?;
     refresh_user_rewards(user_stake, stake_vault)?;
 
-    // Get the unstake request
-    let unstake_request = &user_stake.unstake_requests[request_index];
+    let unstake_request = &mut ctx.accounts.unstake_request;
 
-    // Calculate remaining unclaimed amount (this is what gets returned to active stake)
-    let remaining_amount = unstake_request
-        .total_amount
-        .checked_sub(unstake_request.claimed_amount)
-        .ok_or(ErrorCode::MathOverflow)?;
+    // Remaining unclaimed amount is the most that can be returned to active stake
+    let remaining_amount = unstake_request.remaining_amount();
 
     require!(remaining_amount > 0, ErrorCode::NoAmountToCancel);
 
-    // Update user stake - move remaining back to active
+    let amount_returned = params.amount.unwrap_or(remaining_amount);
+
+    require!(amount_returned > 0, ErrorCode::InvalidAmount);
+    require!(amount_returned <= remaining_amount, ErrorCode::InsufficientBalance);
+
+    // The rest vests over the request's original start and end time, never sooner
+    unstake_request.cancelled_amount = unstake_request
+        .cancelled_amount
+        .checked_add(amount_returned)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let request_remaining_amount = remaining_amount - amount_returned;
+
+    // Update user stake - move the cancelled amount back to active
     user_stake.active_stake_amount = user_stake
         .active_stake_amount
-        .checked_add(remaining_amount)
+        .checked_add(amount_returned)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    user_stake.unstaking_amount = user_stake
+        .unstaking_amount
+        .checked_sub(amount_returned)
         .ok_or(ErrorCode::MathOverflow)?;
 
     // Update vault stats
     stake_vault.stake_stats.active_amount = stake_vault
         .stake_stats
         .active_amount
-        .checked_add(remaining_amount)
+        .checked_add(amount_returned)
         .ok_or(ErrorCode::MathOverflow)?;
 
     stake_vault.stake_stats.unstaking_amount = stake_vault
         .stake_stats
         .unstaking_amount
-        .checked_sub(remaining_amount)
+        .checked_sub(amount_returned)
         .ok_or(ErrorCode::MathOverflow)?;
 
-    // Remove the unstake request
-    user_stake.unstake_requests.remove(request_index);
-
-    // Update reward snapshot after stake change
-    update_reward_snapshot_after_stake_change(user_stake, stake_vault)?;
+    // Close the unstake request once nothing is left to vest
+    if request_remaining_amount == 0 {
+        unstake_request.close(ctx.accounts.owner.to_account_info())?;
+        user_stake.open_unstake_requests = user_stake
+            .open_unstake_requests
+            .checked_sub(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    // Update weighted stake and reward snapshot after stake change
+    update_reward_snapshot_after_stake_change(user_stake, stake_vault, current_time)?;
+    record_voting_power(
+        &mut ctx.accounts.voting_power_record,
+        user_stake,
+        stake_vault,
+        ctx.bumps.voting_power_record,
+    )?;
 
     user_stake.last_update_timestamp = current_time;
 
     emit_cpi!(UnstakeCancelled {
         user: ctx.accounts.owner.key(),
-        request_index: params.request_index,
-        amount_returned: remaining_amount,
+        request_id: params.request_id,
+        amount_returned,
+        remaining_amount: request_remaining_amount,
         timestamp: current_time,
     });
 
@@ -0,0 +1,67 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::{STAKE_VAULT_SEED, USER_STAKE_SEED, VOTING_POWER_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::VotingPowerCheckpointed,
+    state::{StakeVault, UserStake, VotingPowerRecord},
+    instructions::helpers::record_voting_power,
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct CheckpointVotingPower<'info> {
+    /// Owner of the user stake, pays for the record when it is created
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+/// Stake changes checkpoint on their own, this catches up with lockup boosts decaying over time.
+pub fn handler(ctx: Context<CheckpointVotingPower>) -> Result<()> {
+    let checkpoint = record_voting_power(
+        &mut ctx.accounts.voting_power_record,
+        &ctx.accounts.user_stake,
+        &ctx.accounts.stake_vault,
+        ctx.bumps.voting_power_record,
+    )?;
+
+    emit_cpi!(VotingPowerCheckpointed {
+        user: ctx.accounts.user_stake.owner,
+        stake_vault: ctx.accounts.stake_vault.key(),
+        slot: checkpoint.slot,
+        voting_power: checkpoint.voting_power,
+        timestamp: checkpoint.timestamp,
+    });
+
+    Ok(())
+}
@@ -1,29 +1,35 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{transfer, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
 
 use crate::{
     constants::{STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED, USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
     error::ErrorCode,
-    events::VestedTokensClaimed,
+    events::{RequestClaim, VestedTokensClaimed},
     state::{StakeVault, UserStake},
+    instructions::helpers::{load_unstake_requests, persist_unstake_requests},
     program::LinearStaking,
 };
 
 #[derive(Accounts)]
 pub struct ClaimVested<'info> {
+    /// The owner or their delegate
+    pub caller: Signer<'info>,
+
+    /// CHECK: owner of the user stake, receives the rent of closed requests
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub owner: UncheckedAccount<'info>,
 
     #[account(
         mut,
-        seeds = [USER_STAKE_SEED, owner.key().as_ref()],
-        bump
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
+        bump,
+        constraint = user_stake.is_owner_or_delegate(&caller.key()) @ ErrorCode::Unauthorized
     )]
     pub user_stake: Account<'info, UserStake>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
@@ -33,24 +39,27 @@ pub struct ClaimVested<'info> {
         constraint = user_token_account.mint == stake_vault.token_mint,
         constraint = user_token_account.owner == owner.key()
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED],
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
         bump
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        seeds = [TRANSFER_AUTHORITY_SEED],
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
         bump
     )]
     pub transfer_authority: AccountInfo<'info>,
 
     pub system_program: Program<'info, System>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 
     /// CHECK: event authority for emit_cpi
     #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
@@ -59,21 +68,37 @@ pub struct ClaimVested<'info> {
     pub program: Program<'info, LinearStaking>,
 }
 
-pub fn handler(ctx: Context<ClaimVested>) -> Result<()> {
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClaimVestedParams {
+    /// Claim at most this amount, in the order requests are passed, everything vested when None
+    pub max_amount: Option<u64>,
+}
+
+/// Claims from the `UnstakeRequest` accounts passed as remaining accounts (writable).
+/// Fully claimed requests are closed and their rent returned to the owner.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClaimVested<'info>>,
+    params: ClaimVestedParams,
+) -> Result<()> {
     let user_stake = &mut ctx.accounts.user_stake;
     let stake_vault = &mut ctx.accounts.stake_vault;
     let current_time = Clock::get()?.unix_timestamp;
 
-    let vesting_period = stake_vault.vesting_period_seconds;
     let mut total_claimable: u64 = 0;
+    let mut claims: Vec<RequestClaim> = Vec::new();
 
     require!(
-        stake_vault.is_paused == false,
+        !stake_vault.is_paused,
         ErrorCode::VaultPaused
     );
 
-    for unstake_request in user_stake.unstake_requests.iter_mut() {
-        let claimable = unstake_request.claimable_amount(current_time, vesting_period);
+    let mut unstake_requests = load_unstake_requests(ctx.remaining_accounts, &user_stake.key())?;
+    let mut remaining_allowance = params.max_amount.unwrap_or(u64::MAX);
+
+    for unstake_request in unstake_requests.iter_mut() {
+        let claimable = unstake_request
+            .claimable_amount(current_time)
+            .min(remaining_allowance);
 
         if claimable > 0 {
             unstake_request.claimed_amount = unstake_request
@@ -83,19 +108,28 @@ pub fn handler(ctx: Context<ClaimVested>) -> Result<()> {
             total_claimable = total_claimable
                 .checked_add(claimable)
                 .ok_or(ErrorCode::MathOverflow)?;
+            remaining_allowance -= claimable;
+
+            claims.push(RequestClaim {
+                request_id: unstake_request.id,
+                amount: claimable,
+            });
         }
     }
 
     require!(total_claimable > 0, ErrorCode::NoClaimableAmount);
 
     // Transfer tokens from vault to user
+    let stake_vault_key = stake_vault.key();
     let authority_seeds: &[&[&[u8]]] = &[&[
         TRANSFER_AUTHORITY_SEED,
+        stake_vault_key.as_ref(),
         &[stake_vault.transfer_authority_bump],
     ]];
 
-    let cpi_accounts = Transfer {
+    let cpi_accounts = TransferChecked {
         from: ctx.accounts.vault_token_account.to_account_info(),
+        mint: ctx.accounts.token_mint.to_account_info(),
         to: ctx.accounts.user_token_account.to_account_info(),
         authority: ctx.accounts.transfer_authority.to_account_info(),
     };
@@ -103,7 +137,7 @@ pub fn handler(ctx: Context<ClaimVested>) -> Result<()> {
     let cpi_program = ctx.accounts.token_program.to_account_info();
     let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, authority_seeds);
 
-    transfer(cpi_context, total_claimable)?;
+    transfer_checked(cpi_context, total_claimable, ctx.accounts.token_mint.decimals)?;
 
     // Update vault stats
     stake_vault.stake_stats.unstaking_amount = stake_vault
@@ -135,13 +169,24 @@ pub fn handler(ctx: Context<ClaimVested>) -> Result<()> {
         .checked_sub(total_claimable)
         .ok_or(ErrorCode::MathOverflow)?;
 
-    user_stake.cleanup_claimed_requests();
+    user_stake.unstaking_amount = user_stake
+        .unstaking_amount
+        .checked_sub(total_claimable)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let closed_count = persist_unstake_requests(unstake_requests, &ctx.accounts.owner.to_account_info())?;
+    user_stake.open_unstake_requests = user_stake
+        .open_unstake_requests
+        .checked_sub(closed_count)
+        .ok_or(ErrorCode::MathOverflow)?;
+
     user_stake.last_update_timestamp = current_time;
 
     emit_cpi!(VestedTokensClaimed {
         user: ctx.accounts.owner.key(),
         amount: total_claimable,
-        remaining_unstaking: user_stake.get_total_unstaking_amount(),
+        remaining_unstaking: user_stake.unstaking_amount,
+        claims,
         timestamp: current_time,
     });
 
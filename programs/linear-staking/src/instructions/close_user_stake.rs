@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::{STAKE_VAULT_SEED, USER_STAKE_SEED, VOTING_POWER_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::UserStakeClosed,
+    state::{StakeVault, UserStake, VotingPowerRecord},
+    instructions::helpers::{refresh_user_rewards, settle_expired_stake_locks},
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct CloseUserStake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    /// Created alongside the user stake, its rent is returned too
+    #[account(
+        mut,
+        close = owner,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump = voting_power_record.bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+/// A user stake created again at the same address gets a new generation, so its
+/// unstake requests never land on the addresses of the closed stake's requests.
+pub fn handler(ctx: Context<CloseUserStake>) -> Result<()> {
+    let user_stake = &mut ctx.accounts.user_stake;
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(
+        user_stake.staked_amount == 0 && user_stake.open_unstake_requests == 0,
+        ErrorCode::UserStakeNotEmpty
+    );
+
+    // Make sure every reward earned has been accounted for before checking
+    settle_expired_stake_locks(user_stake, stake_vault, current_time)?;
+    stake_vault.update_reward_streams(current_time)?;
+    refresh_user_rewards(user_stake, stake_vault)?;
+
+    require!(
+        user_stake
+            .reward_streams
+            .iter()
+            .all(|user_reward| user_reward.unclaimed_rewards == 0),
+        ErrorCode::UnclaimedRewardsRemaining
+    );
+
+    emit_cpi!(UserStakeClosed {
+        user: ctx.accounts.owner.key(),
+        stake_vault: stake_vault.key(),
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
@@ -1,57 +1,65 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{transfer, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
 
 use crate::{
-    constants::{STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED, USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
+    constants::{STAKE_VAULT_SEED, TRANSFER_AUTHORITY_SEED, USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
     error::ErrorCode,
     events::RewardsCollected,
     state::{StakeVault, UserStake},
-    instructions::helpers::refresh_user_rewards,
+    instructions::helpers::{refresh_user_rewards, settle_expired_stake_locks},
     program::LinearStaking,
 };
 
+/// Collects rewards of the stream paying out of `reward_token_account`.
+/// Additional streams can be collected in the same instruction by passing
+/// `(reward_token_account, user_token_account, reward_mint)` triples as remaining
+/// accounts. All collected streams must be owned by the same token program.
 #[derive(Accounts)]
 pub struct CollectRewards<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    /// The owner or their delegate
+    pub caller: Signer<'info>,
+
+    /// CHECK: owner of the user stake, rewards are only paid to their token accounts
+    pub owner: UncheckedAccount<'info>,
 
     #[account(
         mut,
-        seeds = [USER_STAKE_SEED, owner.key().as_ref()],
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
         bump = user_stake.bump,
-        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized,
+        constraint = user_stake.is_owner_or_delegate(&caller.key()) @ ErrorCode::Unauthorized
     )]
     pub user_stake: Account<'info, UserStake>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump = stake_vault.bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
 
     #[account(
         mut,
-        constraint = user_token_account.mint == stake_vault.token_mint,
+        constraint = user_token_account.mint == reward_token_account.mint,
         constraint = user_token_account.owner == owner.key()
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(
-        mut,
-        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED],
-        bump = stake_vault.token_account_bump
-    )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    /// Reward token account of the stream being collected
+    #[account(mut)]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = reward_token_account.mint)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
 
     /// CHECK: PDA used as transfer authority
     #[account(
-        seeds = [TRANSFER_AUTHORITY_SEED],
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
         bump = stake_vault.transfer_authority_bump
     )]
     pub transfer_authority: AccountInfo<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 
     /// CHECK: event authority for emit_cpi
     #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
@@ -60,63 +68,122 @@ pub struct CollectRewards<'info> {
     pub program: Program<'info, LinearStaking>,
 }
 
-pub fn handler(ctx: Context<CollectRewards>) -> Result<()> {
+pub fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, CollectRewards<'info>>) -> Result<()> {
     let user_stake = &mut ctx.accounts.user_stake;
     let stake_vault = &mut ctx.accounts.stake_vault;
     let current_time = Clock::get()?.unix_timestamp;
 
+    // Rewards owed before an emergency stay collectable
     require!(
-        !stake_vault.is_paused,
+        !stake_vault.is_paused || stake_vault.is_emergency,
         ErrorCode::VaultPaused
     );
 
-    // Refresh rewards to calculate latest unclaimed amount
-    refresh_user_rewards(user_stake, stake_vault)?;
-
-    let rewards_to_claim = user_stake.reward_state.unclaimed_rewards;
+    require!(
+        ctx.remaining_accounts.len() % 3 == 0,
+        ErrorCode::InvalidRewardTokenAccount
+    );
 
-    require!(rewards_to_claim > 0, ErrorCode::NoRewardsToClaim);
+    // Stream rewards up to now and refresh to calculate latest unclaimed amounts
+    settle_expired_stake_locks(user_stake, stake_vault, current_time)?;
+    stake_vault.update_reward_streams(current_time)?;
+    refresh_user_rewards(user_stake, stake_vault)?;
 
-    // Transfer rewards from vault to user
+    // Collect the (reward token account, user token account, mint) of every requested stream
+    let mut payouts: Vec<(usize, AccountInfo<'info>, AccountInfo<'info>, AccountInfo<'info>, u8)> = vec![(
+        stake_vault.reward_stream_index(&ctx.accounts.reward_token_account.key())?,
+        ctx.accounts.reward_token_account.to_account_info(),
+        ctx.accounts.user_token_account.to_account_info(),
+        ctx.accounts.reward_mint.to_account_info(),
+        ctx.accounts.reward_mint.decimals,
+    )];
+
+    for accounts in ctx.remaining_accounts.chunks(3) {
+        let stream_index = stake_vault.reward_stream_index(&accounts[0].key())?;
+        let user_token_account = InterfaceAccount::<TokenAccount>::try_from(&accounts[1])?;
+        let reward_mint = InterfaceAccount::<Mint>::try_from(&accounts[2])?;
+
+        require!(
+            reward_mint.key() == stake_vault.reward_streams[stream_index].mint
+                && user_token_account.mint == reward_mint.key()
+                && user_token_account.owner == ctx.accounts.owner.key(),
+            ErrorCode::InvalidRewardTokenAccount
+        );
+        require!(
+            payouts.iter().all(|(index, ..)| *index != stream_index),
+            ErrorCode::InvalidRewardStream
+        );
+
+        payouts.push((
+            stream_index,
+            accounts[0].clone(),
+            accounts[1].clone(),
+            accounts[2].clone(),
+            reward_mint.decimals,
+        ));
+    }
+
+    let stake_vault_key = stake_vault.key();
     let authority_seeds: &[&[&[u8]]] = &[&[
         TRANSFER_AUTHORITY_SEED,
+        stake_vault_key.as_ref(),
         &[stake_vault.transfer_authority_bump],
     ]];
 
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.vault_token_account.to_account_info(),
-        to: ctx.accounts.user_token_account.to_account_info(),
-        authority: ctx.accounts.transfer_authority.to_account_info(),
-    };
+    let mut collected_any = false;
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, authority_seeds);
+    for (stream_index, reward_token_account, user_token_account, reward_mint, decimals) in payouts {
+        let rewards_to_claim = user_stake.reward_streams[stream_index].unclaimed_rewards;
 
-    transfer(cpi_context, rewards_to_claim)?;
+        if rewards_to_claim == 0 {
+            continue;
+        }
 
-    // Update user reward state
-    user_stake.reward_state.total_claimed = user_stake
-        .reward_state
-        .total_claimed
-        .checked_add(rewards_to_claim)
-        .ok_or(ErrorCode::MathOverflow)?;
+        // Transfer rewards from the reward token account to user
+        let cpi_accounts = TransferChecked {
+            from: reward_token_account,
+            mint: reward_mint,
+            to: user_token_account,
+            authority: ctx.accounts.transfer_authority.to_account_info(),
+        };
 
-    user_stake.reward_state.unclaimed_rewards = 0;
-    user_stake.last_update_timestamp = current_time;
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, authority_seeds);
+
+        transfer_checked(cpi_context, rewards_to_claim, decimals)?;
+
+        // Update user reward state
+        let user_reward = &mut user_stake.reward_streams[stream_index];
+
+        user_reward.total_claimed = user_reward
+            .total_claimed
+            .checked_add(rewards_to_claim)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        user_reward.unclaimed_rewards = 0;
+
+        // Update vault reward state
+        let reward_stream = &mut stake_vault.reward_streams[stream_index];
 
-    // Update vault reward state
-    stake_vault.reward_state.total_claimed = stake_vault
-        .reward_state
-        .total_claimed
-        .checked_add(rewards_to_claim as u128)
-        .ok_or(ErrorCode::MathOverflow)?;
-
-    emit_cpi!(RewardsCollected {
-        user: ctx.accounts.owner.key(),
-        amount: rewards_to_claim,
-        total_claimed: user_stake.reward_state.total_claimed,
-        timestamp: current_time,
-    });
+        reward_stream.total_claimed = reward_stream
+            .total_claimed
+            .checked_add(rewards_to_claim as u128)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit_cpi!(RewardsCollected {
+            user: ctx.accounts.owner.key(),
+            reward_mint: reward_stream.mint,
+            amount: rewards_to_claim,
+            total_claimed: user_reward.total_claimed,
+            timestamp: current_time,
+        });
+
+        collected_any = true;
+    }
+
+    require!(collected_any, ErrorCode::NoRewardsToClaim);
+
+    user_stake.last_update_timestamp = current_time;
 
     Ok(())
 }
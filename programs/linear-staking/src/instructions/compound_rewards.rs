@@ -0,0 +1,193 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::{
+        STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, REWARD_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED,
+        USER_STAKE_SEED, VOTING_POWER_SEED, EVENT_AUTHORITY_SEED,
+    },
+    error::ErrorCode,
+    events::RewardsCompounded,
+    state::{StakeVault, UserStake, VotingPowerRecord},
+    instructions::helpers::{
+        record_voting_power, refresh_user_rewards, settle_expired_stake_locks,
+        transfer_checked_received,
+        update_reward_snapshot_after_stake_change,
+    },
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct CompoundRewards<'info> {
+    /// The owner or their delegate, or anyone once the owner opted into auto-compounding.
+    /// Pays rent for the voting power record if the user has none yet
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), user_stake.owner.as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.is_owner_or_delegate(&caller.key()) || user_stake.auto_compound @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(
+        init_if_needed,
+        payer = caller,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Token account of the reward stream paid in the staked mint
+    #[account(
+        mut,
+        seeds = [REWARD_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref(), stake_vault.token_mint.as_ref()],
+        bump
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.token_account_bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA used as transfer authority
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+/// Restake the user's unclaimed rewards of the stream paid in the staked mint.
+/// The rewards move from the stream's token account into the vault, the user never holds them.
+pub fn handler(ctx: Context<CompoundRewards>) -> Result<()> {
+    let user_stake = &mut ctx.accounts.user_stake;
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(
+        !stake_vault.is_paused,
+        ErrorCode::VaultPaused
+    );
+
+    if !stake_vault.permissions.allow_deposits {
+        return Err(ErrorCode::DepositsNotAllowed.into());
+    }
+
+    // Only rewards paid in the staked mint can become stake
+    let stream_index = stake_vault.reward_stream_index(&ctx.accounts.reward_token_account.key())?;
+    require!(
+        stake_vault.compounding_stream_index() == Some(stream_index),
+        ErrorCode::InvalidRewardStream
+    );
+
+    // Stream rewards up to now, then refresh user rewards before changing stake
+    settle_expired_stake_locks(user_stake, stake_vault, current_time)?;
+    stake_vault.update_reward_streams(current_time)?;
+    refresh_user_rewards(user_stake, stake_vault)?;
+
+    let amount = user_stake.reward_streams[stream_index].unclaimed_rewards;
+    require!(amount > 0, ErrorCode::NoRewardsToClaim);
+
+    let stake_vault_key = stake_vault.key();
+    let authority_seeds: &[&[&[u8]]] = &[&[
+        TRANSFER_AUTHORITY_SEED,
+        stake_vault_key.as_ref(),
+        &[stake_vault.transfer_authority_bump],
+    ]];
+
+    // Only what the vault actually received becomes stake, transfer fees are not credited
+    let amount_received = transfer_checked_received(
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.reward_token_account.to_account_info(),
+        &mut ctx.accounts.vault_token_account,
+        &ctx.accounts.token_mint,
+        ctx.accounts.transfer_authority.to_account_info(),
+        authority_seeds,
+        amount,
+    )?;
+
+    require!(amount_received > 0, ErrorCode::InvalidAmount);
+
+    // Rewards count as claimed by the user
+    let user_reward = &mut user_stake.reward_streams[stream_index];
+    user_reward.unclaimed_rewards = 0;
+    user_reward.total_claimed = user_reward
+        .total_claimed
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let reward_stream = &mut stake_vault.reward_streams[stream_index];
+    reward_stream.total_claimed = reward_stream
+        .total_claimed
+        .checked_add(amount as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // ...and join the active stake
+    user_stake.staked_amount = user_stake
+        .staked_amount
+        .checked_add(amount_received)
+        .ok_or(ErrorCode::MathOverflow)?;
+    user_stake.active_stake_amount = user_stake
+        .active_stake_amount
+        .checked_add(amount_received)
+        .ok_or(ErrorCode::MathOverflow)?;
+    user_stake.last_update_timestamp = current_time;
+
+    stake_vault.stake_stats.total_staked = stake_vault
+        .stake_stats
+        .total_staked
+        .checked_add(amount_received)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.stake_stats.active_amount = stake_vault
+        .stake_stats
+        .active_amount
+        .checked_add(amount_received)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Update weighted stake and reward snapshot after stake change
+    update_reward_snapshot_after_stake_change(user_stake, stake_vault, current_time)?;
+    record_voting_power(
+        &mut ctx.accounts.voting_power_record,
+        user_stake,
+        stake_vault,
+        ctx.bumps.voting_power_record,
+    )?;
+
+    emit_cpi!(RewardsCompounded {
+        user: user_stake.owner,
+        caller: ctx.accounts.caller.key(),
+        amount: amount_received,
+        active_stake_amount: user_stake.active_stake_amount,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
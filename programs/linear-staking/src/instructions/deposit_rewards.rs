@@ -1,11 +1,12 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{transfer, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::{
-    constants::{STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, EVENT_AUTHORITY_SEED},
+    constants::{STAKE_VAULT_SEED, EVENT_AUTHORITY_SEED},
     error::ErrorCode,
     events::RewardsDeposited,
     state::StakeVault,
+    instructions::helpers::transfer_checked_received,
     program::LinearStaking,
 };
 
@@ -16,26 +17,26 @@ pub struct DepositRewards<'info> {
 
     #[account(
         mut,
-        constraint = admin_token_account.mint == stake_vault.token_mint,
+        constraint = admin_token_account.mint == reward_token_account.mint,
         constraint = admin_token_account.owner == admin.key()
     )]
-    pub admin_token_account: Account<'info, TokenAccount>,
+    pub admin_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
 
-    #[account(
-        mut,
-        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED],
-        bump
-    )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    /// Reward token account of the stream being funded
+    #[account(mut)]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    #[account(address = reward_token_account.mint)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
 
     /// CHECK: event authority for emit_cpi
     #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
@@ -47,15 +48,17 @@ pub struct DepositRewards<'info> {
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct DepositRewardsParams {
     pub amount: u64,
+    /// Period over which the deposited rewards (plus any unstreamed leftover) are emitted
+    pub duration_seconds: u64,
 }
 
 pub fn handler(ctx: Context<DepositRewards>, params: DepositRewardsParams) -> Result<()> {
 
     let stake_vault = &mut ctx.accounts.stake_vault;
-    let amount = params.amount;
+    let current_time = Clock::get()?.unix_timestamp;
 
     require!(
-        ctx.accounts.admin.key() == stake_vault.admin,
+        ctx.accounts.admin.key() == stake_vault.roles.reward_depositor,
         ErrorCode::Unauthorized
     );
 
@@ -64,29 +67,38 @@ pub fn handler(ctx: Context<DepositRewards>, params: DepositRewardsParams) -> Re
         ErrorCode::VaultPaused
     );
 
-    require!(amount > 0, ErrorCode::InvalidAmount);
+    require!(params.amount > 0, ErrorCode::InvalidAmount);
+    require!(params.duration_seconds > 0, ErrorCode::InvalidRewardDuration);
+
+    let stream_index = stake_vault.reward_stream_index(&ctx.accounts.reward_token_account.key())?;
 
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.admin_token_account.to_account_info(),
-        to: ctx.accounts.vault_token_account.to_account_info(),
-        authority: ctx.accounts.admin.to_account_info(),
-    };
+    // Only stream what the reward account actually received
+    let amount = transfer_checked_received(
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.admin_token_account.to_account_info(),
+        &mut ctx.accounts.reward_token_account,
+        &ctx.accounts.reward_mint,
+        ctx.accounts.admin.to_account_info(),
+        &[],
+        params.amount,
+    )?;
+
+    require!(amount > 0, ErrorCode::InvalidAmount);
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-    transfer(cpi_ctx, amount)?;
+    // Settle the current period before rescheduling the stream
+    stake_vault.update_reward_streams(current_time)?;
 
-    stake_vault.reward_state.pending_rewards = stake_vault
-        .reward_state
-        .pending_rewards
-        .checked_add(amount as u128)
-        .ok_or(ErrorCode::MathOverflow)?;
+    let reward_stream = &mut stake_vault.reward_streams[stream_index];
+    reward_stream.notify_reward_amount(amount, params.duration_seconds, current_time)?;
 
     emit_cpi!(RewardsDeposited {
         admin: ctx.accounts.admin.key(),
+        reward_mint: reward_stream.mint,
         amount,
-        total_pending: stake_vault.reward_state.pending_rewards,
-        timestamp: Clock::get()?.unix_timestamp,
+        total_pending: reward_stream.pending_rewards,
+        reward_rate: reward_stream.reward_rate,
+        period_finish: reward_stream.period_finish,
+        timestamp: current_time,
     });
 
     Ok(())
@@ -1,12 +1,19 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface};
 
 use crate::{
-    constants::{STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
+    constants::{
+        STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED, USER_STAKE_SEED,
+        VOTING_POWER_SEED, EVENT_AUTHORITY_SEED,
+    },
     error::ErrorCode,
-    events::StakeDeposited,
-    state::{StakeVault, UserStake},
-    instructions::helpers::{refresh_user_rewards, update_reward_snapshot_after_stake_change},
+    events::{LiquidStakeDeposited, StakeDeposited},
+    state::{LockupTier, StakeVault, UserStake, VotingPowerRecord},
+    instructions::helpers::{
+        credit_active_stake, initialize_user_stake, record_voting_power, refresh_user_rewards,
+        settle_expired_stake_locks,
+        transfer_checked_received,
+    },
     program::LinearStaking,
 };
 
@@ -20,34 +27,64 @@ pub struct DepositStake<'info> {
         constraint = user_token_account.mint == stake_vault.token_mint,
         constraint = user_token_account.owner == owner.key()
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED],
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
         bump
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init_if_needed,
         payer = owner,
         space = 8 + UserStake::INIT_SPACE,
-        seeds = [USER_STAKE_SEED, owner.key().as_ref()],
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
         bump
     )]
     pub user_stake: Account<'info, UserStake>,
 
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    /// Passing the receipt accounts deposits into the liquid pool instead of the user stake
+    #[account(mut, address = stake_vault.liquid_pool.receipt_mint)]
+    pub receipt_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = stake_vault.liquid_pool.receipt_mint,
+        token::authority = owner
+    )]
+    pub user_receipt_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: PDA mint authority of the receipt mint
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: Option<AccountInfo<'info>>,
+
     pub system_program: Program<'info, System>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 
     /// CHECK: event authority for emit_cpi
     #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
@@ -59,15 +96,16 @@ pub struct DepositStake<'info> {
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct DepositStakeParams {
     pub amount: u64,
+    /// Optional lockup on the deposited amount in exchange for a reward weight multiplier
+    pub lockup: Option<LockupTier>,
 }
 
 pub fn handler(ctx: Context<DepositStake>, params: DepositStakeParams) -> Result<()> {
     let stake_vault = &mut ctx.accounts.stake_vault;
     let user_stake = &mut ctx.accounts.user_stake;
-    let amount = params.amount;
     let clock = Clock::get()?;
 
-    require!(amount > 0, ErrorCode::InvalidAmount);
+    require!(params.amount > 0, ErrorCode::InvalidAmount);
     
     require!(!stake_vault.is_paused, ErrorCode::VaultPaused);
 
@@ -75,57 +113,121 @@ pub fn handler(ctx: Context<DepositStake>, params: DepositStakeParams) -> Result
         return Err(ErrorCode::DepositsNotAllowed.into());
     }
 
-    // Refresh user rewards before changing stake amount
+    // Stream rewards up to now, then refresh user rewards before changing stake amount
+    settle_expired_stake_locks(user_stake, stake_vault, clock.unix_timestamp)?;
+    stake_vault.update_reward_streams(clock.unix_timestamp)?;
     refresh_user_rewards(user_stake, stake_vault)?;
 
-    // Transfer tokens from user to vault
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.user_token_account.to_account_info(),
-        to: ctx.accounts.vault_token_account.to_account_info(),
-        authority: ctx.accounts.owner.to_account_info(),
-    };
-
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-
-    transfer(cpi_ctx, amount)?;
-
-    // Update vault stake stats
-    stake_vault.stake_stats.total_staked = stake_vault
-        .stake_stats
-        .total_staked
-        .checked_add(amount)
-        .ok_or(ErrorCode::MathOverflow)?;
-
-    stake_vault.stake_stats.active_amount = stake_vault
-        .stake_stats
-        .active_amount
-        .checked_add(amount)
-        .ok_or(ErrorCode::MathOverflow)?;
-
-    // Initialize or update user stake
-    user_stake.owner = ctx.accounts.owner.key();
-    user_stake.is_initialized = true;
-    user_stake.stake_vault = stake_vault.key();
-    user_stake.bump = ctx.bumps.user_stake;
+    // Transfer tokens from user to vault, crediting what the vault actually received
+    let amount = transfer_checked_received(
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.user_token_account.to_account_info(),
+        &mut ctx.accounts.vault_token_account,
+        &ctx.accounts.token_mint,
+        ctx.accounts.owner.to_account_info(),
+        &[],
+        params.amount,
+    )?;
+
+    require!(amount > 0, ErrorCode::InvalidAmount);
+
+    // Initialize user stake, it also holds unstake requests of liquid stake
+    initialize_user_stake(user_stake, ctx.accounts.owner.key(), stake_vault, ctx.bumps.user_stake)?;
     user_stake.last_update_timestamp = clock.unix_timestamp;
-    user_stake.staked_amount = user_stake
-        .staked_amount
-        .checked_add(amount)
-        .ok_or(ErrorCode::MathOverflow)?;
-    user_stake.active_stake_amount = user_stake
-        .active_stake_amount
-        .checked_add(amount)
-        .ok_or(ErrorCode::MathOverflow)?;
-
-    // Update reward snapshot after stake change
-    update_reward_snapshot_after_stake_change(user_stake, stake_vault)?;
+
+    if let Some(receipt_mint) = &ctx.accounts.receipt_mint {
+        require!(params.lockup.is_none(), ErrorCode::LockupNotAllowed);
+
+        let (Some(user_receipt_token_account), Some(transfer_authority)) = (
+            &ctx.accounts.user_receipt_token_account,
+            &ctx.accounts.transfer_authority,
+        ) else {
+            return Err(ErrorCode::MissingReceiptAccounts.into());
+        };
+
+        // Price the receipts before the deposit joins the pool
+        let receipt_amount = stake_vault
+            .liquid_pool
+            .receipts_for_amount(amount, receipt_mint.supply)?;
+        require!(receipt_amount > 0, ErrorCode::InvalidAmount);
+
+        stake_vault.liquid_pool.pooled_amount = stake_vault
+            .liquid_pool
+            .pooled_amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        stake_vault.stake_stats.total_staked = stake_vault
+            .stake_stats
+            .total_staked
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        stake_vault.stake_stats.active_amount = stake_vault
+            .stake_stats
+            .active_amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let stake_vault_key = stake_vault.key();
+        let authority_seeds: &[&[&[u8]]] = &[&[
+            TRANSFER_AUTHORITY_SEED,
+            stake_vault_key.as_ref(),
+            &[stake_vault.transfer_authority_bump],
+        ]];
+
+        mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: receipt_mint.to_account_info(),
+                    to: user_receipt_token_account.to_account_info(),
+                    authority: transfer_authority.to_account_info(),
+                },
+                authority_seeds,
+            ),
+            receipt_amount,
+        )?;
+
+        // Liquid stake carries no voting power, this only sets up the record
+        record_voting_power(
+            &mut ctx.accounts.voting_power_record,
+            user_stake,
+            stake_vault,
+            ctx.bumps.voting_power_record,
+        )?;
+
+        emit_cpi!(LiquidStakeDeposited {
+            user: ctx.accounts.owner.key(),
+            amount,
+            receipt_amount,
+            pooled_amount: stake_vault.liquid_pool.pooled_amount,
+            receipt_supply: receipt_mint
+                .supply
+                .checked_add(receipt_amount)
+                .ok_or(ErrorCode::MathOverflow)?,
+            timestamp: clock.unix_timestamp,
+        });
+
+        return Ok(());
+    }
+
+    let unlock_time = credit_active_stake(user_stake, stake_vault, amount, params.lockup, clock.unix_timestamp)?;
+
+    record_voting_power(
+        &mut ctx.accounts.voting_power_record,
+        user_stake,
+        stake_vault,
+        ctx.bumps.voting_power_record,
+    )?;
 
     emit_cpi!(StakeDeposited {
         user: ctx.accounts.owner.key(),
         amount,
         total_staked: user_stake.staked_amount,
         active_stake_amount: user_stake.active_stake_amount,
+        weighted_stake_amount: user_stake.weighted_stake_amount,
+        unlock_time,
         timestamp: clock.unix_timestamp,
     });
 
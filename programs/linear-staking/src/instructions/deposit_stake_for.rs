@@ -0,0 +1,142 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::{STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, USER_STAKE_SEED, VOTING_POWER_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::StakeDepositedFor,
+    state::{StakeVault, UserStake, VotingPowerRecord},
+    instructions::helpers::{
+        credit_active_stake, initialize_user_stake, record_voting_power, refresh_user_rewards,
+        settle_expired_stake_locks,
+        transfer_checked_received,
+    },
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct DepositStakeFor<'info> {
+    /// Funds the stake and the beneficiary's user stake rent, gains no control over it
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: any wallet, only used to derive its user stake
+    pub beneficiary: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.mint == stake_vault.token_mint,
+        constraint = payer_token_account.owner == payer.key()
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.token_account_bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + UserStake::INIT_SPACE,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), beneficiary.key().as_ref()],
+        bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    pub system_program: Program<'info, System>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct DepositStakeForParams {
+    pub amount: u64,
+}
+
+/// Stake the payer's tokens into the beneficiary's user stake, e.g. for grants or airdrops.
+/// Only the beneficiary can unstake or collect rewards afterwards. The stake is never
+/// locked, lockups are the beneficiary's own choice through `deposit_stake`.
+pub fn handler(ctx: Context<DepositStakeFor>, params: DepositStakeForParams) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let user_stake = &mut ctx.accounts.user_stake;
+    let clock = Clock::get()?;
+
+    require!(params.amount > 0, ErrorCode::InvalidAmount);
+
+    require!(!stake_vault.is_paused, ErrorCode::VaultPaused);
+
+    if !stake_vault.permissions.allow_deposits {
+        return Err(ErrorCode::DepositsNotAllowed.into());
+    }
+
+    // Stream rewards up to now, then refresh user rewards before changing stake amount
+    settle_expired_stake_locks(user_stake, stake_vault, clock.unix_timestamp)?;
+    stake_vault.update_reward_streams(clock.unix_timestamp)?;
+    refresh_user_rewards(user_stake, stake_vault)?;
+
+    // Transfer tokens from payer to vault, crediting what the vault actually received
+    let amount = transfer_checked_received(
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.payer_token_account.to_account_info(),
+        &mut ctx.accounts.vault_token_account,
+        &ctx.accounts.token_mint,
+        ctx.accounts.payer.to_account_info(),
+        &[],
+        params.amount,
+    )?;
+
+    require!(amount > 0, ErrorCode::InvalidAmount);
+
+    // Initialize user stake for the beneficiary, never for the payer
+    initialize_user_stake(user_stake, ctx.accounts.beneficiary.key(), stake_vault, ctx.bumps.user_stake)?;
+    user_stake.last_update_timestamp = clock.unix_timestamp;
+
+    credit_active_stake(user_stake, stake_vault, amount, None, clock.unix_timestamp)?;
+
+    record_voting_power(
+        &mut ctx.accounts.voting_power_record,
+        user_stake,
+        stake_vault,
+        ctx.bumps.voting_power_record,
+    )?;
+
+    emit_cpi!(StakeDepositedFor {
+        payer: ctx.accounts.payer.key(),
+        beneficiary: ctx.accounts.beneficiary.key(),
+        amount,
+        total_staked: user_stake.staked_amount,
+        active_stake_amount: user_stake.active_stake_amount,
+        weighted_stake_amount: user_stake.weighted_stake_amount,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use anchor_lang::prelude::*;
 
 use crate::{
-    constants::{PRECISION, STAKE_VAULT_SEED, EVENT_AUTHORITY_SEED},
+    constants::{STAKE_VAULT_SEED, EVENT_AUTHORITY_SEED},
     error::ErrorCode,
     events::RewardsDistributed,
     state::StakeVault,
@@ -15,7 +15,7 @@ pub struct DistributeRewards<'info> {
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump = stake_vault.bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
@@ -29,51 +29,46 @@ pub struct DistributeRewards<'info> {
 
 pub fn handler(ctx: Context<DistributeRewards>) -> Result<()> {
     let stake_vault = &mut ctx.accounts.stake_vault;
+    let total_weighted_stake = stake_vault.stake_stats.total_weighted_stake;
+    let pooled_amount = stake_vault.liquid_pool.pooled_amount;
+    let compounding_stream_index = stake_vault.compounding_stream_index();
+    let current_time = Clock::get()?.unix_timestamp;
 
-    let pending = stake_vault.reward_state.pending_rewards;
-    let total_active_stake = stake_vault.stake_stats.active_amount;
-
-    // Check if there are rewards to distribute
-    require!(pending > 0, ErrorCode::NoPendingRewards);
-
-    // Check if there is active stake to distribute to
-    require!(total_active_stake > 0, ErrorCode::NoActiveStake);
     require!(
         !stake_vault.is_paused,
         ErrorCode::VaultPaused
     );
 
-    // Calculate reward per token: (pending * PRECISION) / total_active_stake
-    let reward_increment = pending
-        .checked_mul(PRECISION)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(total_active_stake as u128)
-        .ok_or(ErrorCode::MathOverflow)?;
-
-    // Update global accumulator
-    stake_vault.reward_state.reward_per_token_staked = stake_vault
-        .reward_state
-        .reward_per_token_staked
-        .checked_add(reward_increment)
-        .ok_or(ErrorCode::MathOverflow)?;
+    // Stream every reward stream up to now; the same update also runs lazily
+    // on every stake-changing instruction, this crank just checkpoints it.
+    let distributed_amounts = stake_vault.update_reward_streams(current_time)?;
 
-    // Track total distributed
-    stake_vault.reward_state.total_distributed = stake_vault
-        .reward_state
-        .total_distributed
-        .checked_add(pending)
-        .ok_or(ErrorCode::MathOverflow)?;
+    for (index, (reward_stream, distributed)) in stake_vault
+        .reward_streams
+        .iter()
+        .zip(distributed_amounts)
+        .enumerate()
+    {
+        if distributed == 0 {
+            continue;
+        }
 
-    // Clear pending rewards
-    stake_vault.reward_state.pending_rewards = 0;
+        // The liquid pool only earns from the stream paid in the staked mint
+        let stream_weight = if Some(index) == compounding_stream_index {
+            total_weighted_stake.saturating_add(pooled_amount)
+        } else {
+            total_weighted_stake
+        };
 
-    emit_cpi!(RewardsDistributed {
-        distributor: ctx.accounts.payer.key(),
-        amount: pending,
-        reward_per_token: stake_vault.reward_state.reward_per_token_staked,
-        total_active_stake,
-        timestamp: Clock::get()?.unix_timestamp,
-    });
+        emit_cpi!(RewardsDistributed {
+            distributor: ctx.accounts.payer.key(),
+            reward_mint: reward_stream.mint,
+            amount: distributed,
+            reward_per_token: reward_stream.reward_per_token_staked,
+            total_weighted_stake: stream_weight,
+            timestamp: current_time,
+        });
+    }
 
     Ok(())
 }
@@ -0,0 +1,173 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{
+    constants::{STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED, USER_STAKE_SEED, VOTING_POWER_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::EmergencyExited,
+    state::{StakeVault, UserStake, VotingPowerRecord},
+    instructions::helpers::{
+        load_unstake_requests, record_voting_power, refresh_user_rewards, settle_expired_stake_locks,
+        update_reward_snapshot_after_stake_change,
+    },
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct EmergencyExit<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == stake_vault.token_mint,
+        constraint = user_token_account.owner == owner.key()
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.token_account_bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA used as transfer authority
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+/// Every open `UnstakeRequest` of the user must be passed as a writable remaining
+/// account, they are closed along with the exit.
+pub fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, EmergencyExit<'info>>) -> Result<()> {
+    let user_stake = &mut ctx.accounts.user_stake;
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(stake_vault.is_emergency, ErrorCode::EmergencyModeNotActive);
+
+    let amount = user_stake.staked_amount;
+    require!(amount > 0, ErrorCode::InsufficientBalance);
+
+    // Requests left open could be claimed again after the exit
+    let unstake_requests = load_unstake_requests(ctx.remaining_accounts, &user_stake.key())?;
+    require!(
+        unstake_requests.len() as u64 == user_stake.open_unstake_requests,
+        ErrorCode::MissingUnstakeRequests
+    );
+
+    // Lock in rewards earned so far, they stay collectable after the exit
+    settle_expired_stake_locks(user_stake, stake_vault, current_time)?;
+    stake_vault.update_reward_streams(current_time)?;
+    refresh_user_rewards(user_stake, stake_vault)?;
+
+    let active_amount = user_stake.active_stake_amount;
+    let unstaking_amount = user_stake.unstaking_amount;
+
+    // Update vault stats - active stake and unvested requests leave together, ignoring lockups
+    stake_vault.stake_stats.total_staked = stake_vault
+        .stake_stats
+        .total_staked
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.stake_stats.active_amount = stake_vault
+        .stake_stats
+        .active_amount
+        .checked_sub(active_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.stake_stats.unstaking_amount = stake_vault
+        .stake_stats
+        .unstaking_amount
+        .checked_sub(unstaking_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Update user stake
+    user_stake.staked_amount = 0;
+    user_stake.active_stake_amount = 0;
+    user_stake.unstaking_amount = 0;
+    user_stake.open_unstake_requests = 0;
+    user_stake.stake_locks.clear();
+    user_stake.last_update_timestamp = current_time;
+
+    update_reward_snapshot_after_stake_change(user_stake, stake_vault, current_time)?;
+    record_voting_power(
+        &mut ctx.accounts.voting_power_record,
+        user_stake,
+        stake_vault,
+        ctx.bumps.voting_power_record,
+    )?;
+
+    for unstake_request in unstake_requests {
+        unstake_request.close(ctx.accounts.owner.to_account_info())?;
+    }
+
+    // Transfer the full stake from vault to user
+    let stake_vault_key = stake_vault.key();
+    let authority_seeds: &[&[&[u8]]] = &[&[
+        TRANSFER_AUTHORITY_SEED,
+        stake_vault_key.as_ref(),
+        &[stake_vault.transfer_authority_bump],
+    ]];
+
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.vault_token_account.to_account_info(),
+        mint: ctx.accounts.token_mint.to_account_info(),
+        to: ctx.accounts.user_token_account.to_account_info(),
+        authority: ctx.accounts.transfer_authority.to_account_info(),
+    };
+
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, authority_seeds);
+
+    transfer_checked(cpi_context, amount, ctx.accounts.token_mint.decimals)?;
+
+    emit_cpi!(EmergencyExited {
+        user: ctx.accounts.owner.key(),
+        amount,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{
+    constants::{STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::LiquidStakeEmergencyRedeemed,
+    state::StakeVault,
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct EmergencyRedeem<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(mut, address = stake_vault.liquid_pool.receipt_mint)]
+    pub receipt_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = stake_vault.liquid_pool.receipt_mint,
+        token::authority = owner
+    )]
+    pub user_receipt_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == stake_vault.token_mint,
+        constraint = user_token_account.owner == owner.key()
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.token_account_bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA used as transfer authority
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct EmergencyRedeemParams {
+    /// Receipts to burn
+    pub receipt_amount: u64,
+}
+
+/// Liquid stake has no user stake to exit from, receipts are burned and their share of
+/// the pool is paid out right away. Compounded rewards must be settled into the vault first.
+pub fn handler(ctx: Context<EmergencyRedeem>, params: EmergencyRedeemParams) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(stake_vault.is_emergency, ErrorCode::EmergencyModeNotActive);
+    require!(params.receipt_amount > 0, ErrorCode::InvalidAmount);
+
+    // Compounds nothing new once streams are halted, but keeps the pool in sync
+    stake_vault.update_reward_streams(current_time)?;
+
+    // The vault only holds the pool's settled stake
+    require!(
+        stake_vault.liquid_pool.uncollected_rewards == 0,
+        ErrorCode::LiquidRewardsNotSettled
+    );
+
+    let amount = stake_vault
+        .liquid_pool
+        .amount_for_receipts(params.receipt_amount, ctx.accounts.receipt_mint.supply)?;
+    require!(amount > 0, ErrorCode::InvalidAmount);
+
+    burn(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Burn {
+                mint: ctx.accounts.receipt_mint.to_account_info(),
+                from: ctx.accounts.user_receipt_token_account.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        ),
+        params.receipt_amount,
+    )?;
+
+    // Update vault stats - the redeemed stake leaves the pool and the vault
+    stake_vault.liquid_pool.pooled_amount = stake_vault
+        .liquid_pool
+        .pooled_amount
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.stake_stats.total_staked = stake_vault
+        .stake_stats
+        .total_staked
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.stake_stats.active_amount = stake_vault
+        .stake_stats
+        .active_amount
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let stake_vault_key = stake_vault.key();
+    let authority_seeds: &[&[&[u8]]] = &[&[
+        TRANSFER_AUTHORITY_SEED,
+        stake_vault_key.as_ref(),
+        &[stake_vault.transfer_authority_bump],
+    ]];
+
+    transfer_checked(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault_token_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: ctx.accounts.transfer_authority.to_account_info(),
+            },
+            authority_seeds,
+        ),
+        amount,
+        ctx.accounts.token_mint.decimals,
+    )?;
+
+    emit_cpi!(LiquidStakeEmergencyRedeemed {
+        user: ctx.accounts.owner.key(),
+        receipt_amount: params.receipt_amount,
+        amount,
+        pooled_amount: stake_vault.liquid_pool.pooled_amount,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::{STAKE_VAULT_SEED, USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::StakeLocksExpired,
+    state::{StakeVault, UserStake},
+    instructions::helpers::settle_expired_stake_locks,
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct ExpireStakeLocks<'info> {
+    /// Anyone can crank this instruction
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(
+        mut,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), user_stake.owner.as_ref()],
+        bump = user_stake.bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+/// Expired boosts keep diluting other stakers until the stake is touched, this drops
+/// them from the vault's weighted stake as close to their unlock time as possible.
+pub fn handler(ctx: Context<ExpireStakeLocks>) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let user_stake = &mut ctx.accounts.user_stake;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(
+        !stake_vault.is_paused,
+        ErrorCode::VaultPaused
+    );
+
+    require!(
+        user_stake.stake_locks.iter().any(|lock| lock.is_expired(current_time)),
+        ErrorCode::NoExpiredStakeLocks
+    );
+
+    settle_expired_stake_locks(user_stake, stake_vault, current_time)?;
+
+    emit_cpi!(StakeLocksExpired {
+        user: user_stake.owner,
+        stake_vault: stake_vault.key(),
+        weighted_stake_amount: user_stake.weighted_stake_amount,
+        total_weighted_stake: stake_vault.stake_stats.total_weighted_stake,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
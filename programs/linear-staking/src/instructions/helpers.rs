@@ -1,68 +1,544 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer};
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TransferChecked};
 
 use crate::{
-    constants::PRECISION,
+    constants::{PRECISION, UNSTAKE_REQUEST_SEED},
     error::ErrorCode,
-    state::{StakeVault, UserStake},
+    state::{
+        LockupTier, StakeLock, StakeVault, UnstakeRequest, UserRewardState, UserStake, VotingPowerCheckpoint,
+        VotingPowerRecord,
+    },
 };
 
-/// Refresh a user's reward state based on current global accumulator.
+/// Transfer `amount` into `to` and return what it actually received, transfer-fee
+/// mints withhold part of it. `signer_seeds` is empty when `authority` signs itself.
+pub fn transfer_checked_received<'info>(
+    token_program: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    to: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<u64> {
+    let balance_before = to.amount;
+
+    let cpi_accounts = TransferChecked {
+        from,
+        mint: mint.to_account_info(),
+        to: to.to_account_info(),
+        authority,
+    };
+    transfer_checked(
+        CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds),
+        amount,
+        mint.decimals,
+    )?;
+
+    to.reload()?;
+    to.amount
+        .checked_sub(balance_before)
+        .ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Set up a user stake created by `init_if_needed`, an existing one is left as is
+pub fn initialize_user_stake(
+    user_stake: &mut UserStake,
+    owner: Pubkey,
+    stake_vault: &mut Account<StakeVault>,
+    bump: u8,
+) -> Result<()> {
+    if user_stake.is_initialized {
+        return Ok(());
+    }
+
+    user_stake.owner = owner;
+    user_stake.is_initialized = true;
+    user_stake.stake_vault = stake_vault.key();
+    user_stake.bump = bump;
+
+    user_stake.generation = stake_vault.user_stakes_created;
+    stake_vault.user_stakes_created = stake_vault
+        .user_stakes_created
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+/// Credit `amount` received by the vault to the user's active stake, locking it
+/// when a lockup tier is given. Rewards must be refreshed first.
+/// Returns the unlock time of the new lockup.
+pub fn credit_active_stake(
+    user_stake: &mut UserStake,
+    stake_vault: &mut StakeVault,
+    amount: u64,
+    lockup: Option<LockupTier>,
+    current_time: i64,
+) -> Result<Option<i64>> {
+    // Update vault stake stats
+    stake_vault.stake_stats.total_staked = stake_vault
+        .stake_stats
+        .total_staked
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.stake_stats.active_amount = stake_vault
+        .stake_stats
+        .active_amount
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Update user stake
+    user_stake.staked_amount = user_stake
+        .staked_amount
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    user_stake.active_stake_amount = user_stake
+        .active_stake_amount
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let unlock_time = match lockup {
+        Some(lockup) => {
+            let unlock_time = current_time
+                .checked_add(lockup.duration())
+                .ok_or(ErrorCode::MathOverflow)?;
+
+            user_stake.add_stake_lock(StakeLock {
+                amount,
+                start_time: current_time,
+                unlock_time,
+                multiplier_bps: lockup.multiplier_bps(),
+            })?;
+
+            Some(unlock_time)
+        }
+        None => None,
+    };
+
+    // Update weighted stake and reward snapshot after stake change
+    update_reward_snapshot_after_stake_change(user_stake, stake_vault, current_time)?;
+
+    Ok(unlock_time)
+}
+
+/// Load the unstake requests passed as remaining accounts, making sure each
+/// belongs to `user_stake` and appears only once
+pub fn load_unstake_requests<'info>(
+    remaining_accounts: &'info [AccountInfo<'info>],
+    user_stake: &Pubkey,
+) -> Result<Vec<Account<'info, UnstakeRequest>>> {
+    let mut unstake_requests: Vec<Account<'info, UnstakeRequest>> = Vec::with_capacity(remaining_accounts.len());
+
+    for account_info in remaining_accounts {
+        require!(
+            unstake_requests.iter().all(|loaded| loaded.key() != account_info.key()),
+            ErrorCode::InvalidUnstakeRequestId
+        );
+
+        let unstake_request = Account::<UnstakeRequest>::try_from(account_info)?;
+        require_keys_eq!(
+            unstake_request.user_stake,
+            *user_stake,
+            ErrorCode::InvalidUnstakeRequestId
+        );
+
+        unstake_requests.push(unstake_request);
+    }
+
+    Ok(unstake_requests)
+}
+
+/// Write back updated unstake requests, closing fully claimed ones and sending
+/// their rent to `rent_destination`. Returns the number of requests closed.
+pub fn persist_unstake_requests<'info>(
+    unstake_requests: Vec<Account<'info, UnstakeRequest>>,
+    rent_destination: &AccountInfo<'info>,
+) -> Result<u64> {
+    let mut closed_count: u64 = 0;
+
+    for unstake_request in unstake_requests {
+        if unstake_request.is_fully_claimed() {
+            unstake_request.close(rent_destination.clone())?;
+            closed_count += 1;
+        } else {
+            unstake_request.exit(&crate::ID)?;
+        }
+    }
+
+    Ok(closed_count)
+}
+
+/// Create the PDA account of `unstake_request` at `request_info`, funded by `payer`.
+/// For requests created outside of `unstake_request`, where the accounts are only
+/// known at runtime. The address must match the request's user stake, its generation and id.
+pub fn create_unstake_request_account<'info>(
+    mut unstake_request: UnstakeRequest,
+    generation: u64,
+    request_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let generation_bytes = generation.to_le_bytes();
+    let id_bytes = unstake_request.id.to_le_bytes();
+    let (expected_key, bump) = Pubkey::find_program_address(
+        &[
+            UNSTAKE_REQUEST_SEED,
+            unstake_request.user_stake.as_ref(),
+            generation_bytes.as_ref(),
+            id_bytes.as_ref(),
+        ],
+        &crate::ID,
+    );
+    require_keys_eq!(expected_key, request_info.key(), ErrorCode::InvalidUnstakeRequestId);
+
+    let request_seeds: &[&[&[u8]]] = &[&[
+        UNSTAKE_REQUEST_SEED,
+        unstake_request.user_stake.as_ref(),
+        generation_bytes.as_ref(),
+        id_bytes.as_ref(),
+        &[bump],
+    ]];
+
+    let request_space = 8 + UnstakeRequest::INIT_SPACE;
+    let rent_exempt_lamports = Rent::get()?.minimum_balance(request_space);
+    let current_lamports = request_info.lamports();
+
+    if current_lamports == 0 {
+        create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                CreateAccount {
+                    from: payer.clone(),
+                    to: request_info.clone(),
+                },
+                request_seeds,
+            ),
+            rent_exempt_lamports,
+            request_space as u64,
+            &crate::ID,
+        )?;
+    } else {
+        // Lamports sent to the address beforehand would make create_account fail,
+        // top it up to rent exemption and allocate it in place like Anchor's init does
+        let missing_lamports = rent_exempt_lamports.saturating_sub(current_lamports);
+        if missing_lamports > 0 {
+            transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    Transfer {
+                        from: payer.clone(),
+                        to: request_info.clone(),
+                    },
+                ),
+                missing_lamports,
+            )?;
+        }
+
+        allocate(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                Allocate {
+                    account_to_allocate: request_info.clone(),
+                },
+                request_seeds,
+            ),
+            request_space as u64,
+        )?;
+
+        assign(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                Assign {
+                    account_to_assign: request_info.clone(),
+                },
+                request_seeds,
+            ),
+            &crate::ID,
+        )?;
+    }
+
+    unstake_request.bump = bump;
+    let mut data = request_info.try_borrow_mut_data()?;
+    unstake_request.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+/// Make sure the user has a reward entry for every stream of the vault.
+/// Streams added after the user's last update start from a zero snapshot,
+/// which is correct because the user's stake has not changed since then.
+fn sync_user_reward_streams(user_stake: &mut UserStake, stake_vault: &StakeVault) {
+    let stream_count = stake_vault.reward_streams.len();
+    if user_stake.reward_streams.len() < stream_count {
+        user_stake
+            .reward_streams
+            .resize(stream_count, UserRewardState::default());
+    }
+}
+
+/// Refresh a user's reward state based on current global accumulators.
 /// Must be called BEFORE any stake amount changes.
 pub fn refresh_user_rewards(
     user_stake: &mut UserStake,
     stake_vault: &StakeVault,
 ) -> Result<()> {
-    let global_reward_per_token = stake_vault.reward_state.reward_per_token_staked;
-
-    // Calculate current watermark for user's stake
-    // watermark = stake * global_rate / PRECISION
-    let current_watermark = (user_stake.active_stake_amount as u128)
-        .checked_mul(global_reward_per_token)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(PRECISION)
-        .ok_or(ErrorCode::MathOverflow)?;
+    sync_user_reward_streams(user_stake, stake_vault);
 
-    if user_stake.active_stake_amount == 0 {
-        // No stake, set snapshot to 0 (correct watermark for 0 stake)
-        // When user deposits, update_reward_snapshot_after_stake_change must be called
-        user_stake.reward_state.reward_snapshot = current_watermark;
-        return Ok(());
+    let weighted_stake_amount = user_stake.weighted_stake_amount;
+
+    for (stream, user_reward) in stake_vault
+        .reward_streams
+        .iter()
+        .zip(user_stake.reward_streams.iter_mut())
+    {
+        let global_reward_per_token = stream.reward_per_token_staked;
+
+        // Calculate current watermark for user's weighted stake
+        // watermark = weighted stake * global_rate / PRECISION
+        let current_watermark = (weighted_stake_amount as u128)
+            .checked_mul(global_reward_per_token)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(PRECISION)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        if weighted_stake_amount == 0 {
+            // No stake, set snapshot to 0 (correct watermark for 0 stake)
+            // When user deposits, update_reward_snapshot_after_stake_change must be called
+            user_reward.reward_snapshot = current_watermark;
+            continue;
+        }
+
+        // Calculate pending rewards since last snapshot
+        // Both current_watermark and reward_snapshot are in the same units (watermark)
+        let pending_rewards = current_watermark
+            .saturating_sub(user_reward.reward_snapshot) as u64;
+
+        // Add to unclaimed rewards
+        user_reward.unclaimed_rewards = user_reward
+            .unclaimed_rewards
+            .checked_add(pending_rewards)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Update snapshot to current watermark (not raw global_reward_per_token)
+        user_reward.reward_snapshot = current_watermark;
     }
 
-    // Calculate pending rewards since last snapshot
-    // Both current_watermark and reward_snapshot are in the same units (watermark)
-    let pending_rewards = current_watermark
-        .saturating_sub(user_stake.reward_state.reward_snapshot) as u64;
+    Ok(())
+}
 
-    // Add to unclaimed rewards
-    user_stake.reward_state.unclaimed_rewards = user_stake
-        .reward_state
-        .unclaimed_rewards
-        .checked_add(pending_rewards)
-        .ok_or(ErrorCode::MathOverflow)?;
+/// Drop lockups that expired since the user's last update from the user's and the vault's
+/// weighted stake, crediting their boost up to the unlock time. Rewards are streamed up to
+/// each unlock time first, or only up to the vault's last update once it has streamed past
+/// it, so a boost earns past its unlock time only until the stake is touched or poked.
+/// Must be called BEFORE the reward streams are updated to `current_time`.
+pub fn settle_expired_stake_locks(
+    user_stake: &mut UserStake,
+    stake_vault: &mut StakeVault,
+    current_time: i64,
+) -> Result<()> {
+    let mut unlock_times: Vec<i64> = user_stake
+        .stake_locks
+        .iter()
+        .filter(|lock| lock.is_expired(current_time))
+        .map(|lock| lock.unlock_time)
+        .collect();
+    unlock_times.sort_unstable();
+    unlock_times.dedup();
+
+    for unlock_time in unlock_times {
+        let settle_time = unlock_time.max(stake_vault.last_reward_update_time());
 
-    // Update snapshot to current watermark (not raw global_reward_per_token)
-    user_stake.reward_state.reward_snapshot = current_watermark;
+        stake_vault.update_reward_streams(settle_time)?;
+        refresh_user_rewards(user_stake, stake_vault)?;
+        update_reward_snapshot_after_stake_change(user_stake, stake_vault, settle_time)?;
+    }
 
     Ok(())
 }
 
-/// Update user's reward snapshot after stake amount changes.
+/// Recompute the user's weighted stake (dropping expired lockups), carry the
+/// difference into the vault's total and update the user's reward snapshots.
 /// Must be called AFTER stake amount is modified.
 pub fn update_reward_snapshot_after_stake_change(
     user_stake: &mut UserStake,
-    stake_vault: &StakeVault,
+    stake_vault: &mut StakeVault,
+    current_time: i64,
 ) -> Result<()> {
-    let global_reward_per_token = stake_vault.reward_state.reward_per_token_staked;
+    sync_user_reward_streams(user_stake, stake_vault);
+
+    let previous_weighted_stake = user_stake.weighted_stake_amount;
+    let weighted_stake_amount = user_stake.recompute_weighted_stake(current_time)?;
 
-    // Recalculate watermark with new stake amount
-    let new_watermark = (user_stake.active_stake_amount as u128)
-        .checked_mul(global_reward_per_token)
-        .ok_or(ErrorCode::MathOverflow)?
-        .checked_div(PRECISION)
+    stake_vault.stake_stats.total_weighted_stake = stake_vault
+        .stake_stats
+        .total_weighted_stake
+        .checked_add(weighted_stake_amount)
+        .and_then(|total| total.checked_sub(previous_weighted_stake))
         .ok_or(ErrorCode::MathOverflow)?;
 
-    user_stake.reward_state.reward_snapshot = new_watermark;
+    for (stream, user_reward) in stake_vault
+        .reward_streams
+        .iter()
+        .zip(user_stake.reward_streams.iter_mut())
+    {
+        // Recalculate watermark with new weighted stake amount
+        let new_watermark = (weighted_stake_amount as u128)
+            .checked_mul(stream.reward_per_token_staked)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(PRECISION)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        user_reward.reward_snapshot = new_watermark;
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Checkpoint the user's current voting power into their record, created by `init_if_needed`
+/// alongside the user stake and closed with it. Must be called AFTER the active stake or
+/// lockups change, so governance never reads a stale amount from the slot of the change on.
+pub fn record_voting_power(
+    voting_power_record: &mut VotingPowerRecord,
+    user_stake: &Account<UserStake>,
+    stake_vault: &StakeVault,
+    bump: u8,
+) -> Result<VotingPowerCheckpoint> {
+    let clock = Clock::get()?;
+
+    let checkpoint = VotingPowerCheckpoint {
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+        voting_power: user_stake.voting_power(clock.unix_timestamp),
+    };
+
+    voting_power_record.owner = user_stake.owner;
+    voting_power_record.stake_vault = user_stake.stake_vault;
+    voting_power_record.user_stake = user_stake.key();
+    voting_power_record.bump = bump;
+    voting_power_record.push_checkpoint(checkpoint.clone(), stake_vault.voting_power_retention_slot);
+
+    Ok(checkpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{EarlyExitConfig, LiquidPool, RewardState, StakePermissions, StakeStats, VaultRoles};
+
+    /// Vault streaming 10 tokens per second until t=1_000, last updated at `last_update_time`
+    fn stake_vault(total_weighted_stake: u64, last_update_time: i64) -> StakeVault {
+        StakeVault {
+            is_paused: false,
+            is_initialized: true,
+            bump: 0,
+            token_account_bump: 0,
+            transfer_authority_bump: 0,
+            token_mint: Pubkey::default(),
+            vault_token_account: Pubkey::default(),
+            admin: Pubkey::default(),
+            permissions: StakePermissions::default(),
+            vesting_period_seconds: 0,
+            stake_stats: StakeStats {
+                total_weighted_stake,
+                ..StakeStats::default()
+            },
+            reward_streams: vec![RewardState {
+                mint: Pubkey::new_unique(),
+                pending_rewards: 1_000_000,
+                reward_rate: 10 * PRECISION,
+                period_finish: 1_000,
+                last_update_time,
+                ..RewardState::default()
+            }],
+            start_time: 0,
+            collective_unstake_requests_count: 0,
+            cliff_period_seconds: 0,
+            vesting_schedule: Default::default(),
+            early_exit: EarlyExitConfig::default(),
+            pending_admin: Pubkey::default(),
+            roles: VaultRoles::default(),
+            is_emergency: false,
+            liquid_pool: LiquidPool::default(),
+            voting_power_retention_slot: None,
+            user_stakes_created: 1,
+        }
+    }
+
+    /// 1_000 active stake, locked at 1.25x until `unlock_time` for a weight of 1_250
+    fn boosted_user_stake(unlock_time: i64) -> UserStake {
+        UserStake {
+            owner: Pubkey::default(),
+            is_initialized: true,
+            stake_vault: Pubkey::default(),
+            staked_amount: 1_000,
+            active_stake_amount: 1_000,
+            weighted_stake_amount: 1_250,
+            vested_stake_amount: 0,
+            unstaking_amount: 0,
+            open_unstake_requests: 0,
+            stake_locks: vec![StakeLock {
+                amount: 1_000,
+                start_time: 0,
+                unlock_time,
+                multiplier_bps: 12_500,
+            }],
+            unstake_request_count: 0,
+            generation: 0,
+            reward_streams: vec![UserRewardState::default()],
+            last_update_timestamp: 0,
+            bump: 0,
+            auto_compound: false,
+            delegate: None,
+            padding: [0; 7],
+        }
+    }
+
+    #[test]
+    fn expired_boost_earns_up_to_its_unlock_time() {
+        // Another 1_000 is staked unboosted
+        let mut stake_vault = stake_vault(2_250, 0);
+        let mut user_stake = boosted_user_stake(100);
+
+        settle_expired_stake_locks(&mut user_stake, &mut stake_vault, 200).unwrap();
+
+        // The boost leaves the vault's total at its unlock time
+        assert!(user_stake.stake_locks.is_empty());
+        assert_eq!(user_stake.weighted_stake_amount, 1_000);
+        assert_eq!(stake_vault.stake_stats.total_weighted_stake, 2_000);
+        assert_eq!(stake_vault.last_reward_update_time(), 100);
+
+        stake_vault.update_reward_streams(200).unwrap();
+        refresh_user_rewards(&mut user_stake, &stake_vault).unwrap();
+
+        // 1_250 / 2_250 of the first 1_000 rewards, then 1_000 / 2_000 of the next 1_000
+        assert_eq!(user_stake.reward_streams[0].unclaimed_rewards, 555 + 500);
+    }
+
+    #[test]
+    fn expired_boost_streamed_past_is_settled_at_the_last_update() {
+        let mut stake_vault = stake_vault(2_250, 150);
+        let mut user_stake = boosted_user_stake(100);
+
+        settle_expired_stake_locks(&mut user_stake, &mut stake_vault, 200).unwrap();
+
+        assert_eq!(user_stake.weighted_stake_amount, 1_000);
+        assert_eq!(stake_vault.stake_stats.total_weighted_stake, 2_000);
+        assert_eq!(stake_vault.last_reward_update_time(), 150);
+    }
+
+    #[test]
+    fn unexpired_boost_is_left_alone() {
+        let mut stake_vault = stake_vault(2_250, 0);
+        let mut user_stake = boosted_user_stake(300);
+
+        settle_expired_stake_locks(&mut user_stake, &mut stake_vault, 200).unwrap();
+
+        assert_eq!(user_stake.stake_locks.len(), 1);
+        assert_eq!(user_stake.weighted_stake_amount, 1_250);
+        assert_eq!(stake_vault.stake_stats.total_weighted_stake, 2_250);
+        assert_eq!(stake_vault.last_reward_update_time(), 0);
+    }
+}
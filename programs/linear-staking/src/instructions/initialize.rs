@@ -1,13 +1,15 @@
 use anchor_lang::prelude::*;
-use anchor_spl::{token::{Mint, TokenAccount, Token}};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 use crate::{StakeVault, constants::{
     STAKE_VAULT_SEED,
     STAKE_VAULT_TOKEN_ACCOUNT_SEED,
+    REWARD_TOKEN_ACCOUNT_SEED,
     TRANSFER_AUTHORITY_SEED,
     DEFAULT_VESTING_PERIOD,
     EVENT_AUTHORITY_SEED,
-}, StakeStats, RewardState};
-use crate::state::stake_vault::StakePermissions;
+}, StakeStats, RewardState, VestingSchedule};
+use crate::state::stake_vault::{EarlyExitConfig, StakePermissions, VaultRoles};
+use crate::error::ErrorCode;
 use crate::events::VaultInitialized;
 use crate::program::LinearStaking;
 
@@ -16,13 +18,15 @@ pub struct Initialize<'info> {
     #[account(mut)]
     pub admin: Signer<'info>,
 
-    pub token_mint: Account<'info, Mint>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub reward_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         init,
         payer = admin,
         space = 8 + StakeVault::INIT_SPACE,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, token_mint.key().as_ref()],
         bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
@@ -32,20 +36,32 @@ pub struct Initialize<'info> {
         payer = admin,
         token::mint = token_mint,
         token::authority = transfer_authority,
-        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED],
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
         bump
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        seeds = [TRANSFER_AUTHORITY_SEED],
+        init,
+        payer = admin,
+        token::mint = reward_mint,
+        token::authority = transfer_authority,
+        seeds = [REWARD_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref(), reward_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA used as transfer authority
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
         bump
     )]
     pub transfer_authority: AccountInfo<'info>,
 
     pub system_program: Program<'info, System>,
 
-    pub token_program: Program<'info, Token>,
+    /// Token program owning both mints, either SPL Token or Token-2022
+    pub token_program: Interface<'info, TokenInterface>,
 
     /// CHECK: event authority for emit_cpi
     #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
@@ -56,7 +72,9 @@ pub struct Initialize<'info> {
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct InitializeParams {
-    pub vesting_period: Option<i64>,
+    pub vesting_period: Option<u64>,
+    pub cliff_period: Option<u64>,
+    pub vesting_schedule: Option<VestingSchedule>,
 }
 
 pub fn handler(ctx: Context<Initialize>, params: InitializeParams) -> Result<()> {
@@ -71,20 +89,49 @@ pub fn handler(ctx: Context<Initialize>, params: InitializeParams) -> Result<()>
     stake_vault.token_mint = ctx.accounts.token_mint.key();
     stake_vault.vault_token_account = ctx.accounts.vault_token_account.key();
     stake_vault.admin = ctx.accounts.admin.key();
+    stake_vault.pending_admin = Pubkey::default();
+    stake_vault.is_emergency = false;
+    stake_vault.voting_power_retention_slot = None;
+    stake_vault.roles = VaultRoles {
+        pauser: stake_vault.admin,
+        reward_depositor: stake_vault.admin,
+        config_manager: stake_vault.admin,
+        emergency_guardian: stake_vault.admin,
+    };
     stake_vault.permissions = StakePermissions {
         allow_deposits: true,
         allow_withdrawals: true,
     };
-    stake_vault.vesting_period_seconds = params.vesting_period.unwrap_or(DEFAULT_VESTING_PERIOD) as u64;
+    stake_vault.vesting_period_seconds = params.vesting_period.unwrap_or(DEFAULT_VESTING_PERIOD);
+    require!(stake_vault.vesting_period_seconds > 0, ErrorCode::InvalidVestingPeriod);
+    stake_vault.cliff_period_seconds = params.cliff_period.unwrap_or(0);
+
+    stake_vault.vesting_schedule = params.vesting_schedule.unwrap_or_default();
+
+    require!(
+        stake_vault.cliff_period_seconds <= stake_vault.vesting_period_seconds,
+        ErrorCode::InvalidCliffPeriod
+    );
+    stake_vault.vesting_schedule.validate()?;
+    stake_vault.early_exit = EarlyExitConfig::default();
     stake_vault.stake_stats = StakeStats::default();
-    stake_vault.reward_state = RewardState::default();
+    stake_vault.reward_streams = vec![RewardState {
+        mint: ctx.accounts.reward_mint.key(),
+        token_account: ctx.accounts.reward_token_account.key(),
+        token_account_bump: ctx.bumps.reward_token_account,
+        ..RewardState::default()
+    }];
     stake_vault.start_time = Clock::get()?.unix_timestamp;
     stake_vault.collective_unstake_requests_count = 0;
+    stake_vault.user_stakes_created = 0;
 
     emit_cpi!(VaultInitialized {
         admin: ctx.accounts.admin.key(),
         token_mint: ctx.accounts.token_mint.key(),
+        reward_mint: ctx.accounts.reward_mint.key(),
         vesting_period_seconds: stake_vault.vesting_period_seconds,
+        cliff_period_seconds: stake_vault.cliff_period_seconds,
+        vesting_schedule: stake_vault.vesting_schedule,
         timestamp: stake_vault.start_time,
     });
 
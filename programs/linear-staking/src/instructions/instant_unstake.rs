@@ -0,0 +1,243 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{
+    constants::{
+        STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED, UNSTAKE_REQUEST_SEED,
+        USER_STAKE_SEED, EVENT_AUTHORITY_SEED, PENALTY_REWARD_DURATION,
+    },
+    error::ErrorCode,
+    events::InstantUnstaked,
+    state::{StakeVault, UnstakeRequest, UserStake},
+    instructions::helpers::transfer_checked_received,
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+#[instruction(params: InstantUnstakeParams)]
+pub struct InstantUnstake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    /// Released in full, so the request is closed and its rent returned to the owner
+    #[account(
+        mut,
+        close = owner,
+        seeds = [
+            UNSTAKE_REQUEST_SEED,
+            user_stake.key().as_ref(),
+            user_stake.generation.to_le_bytes().as_ref(),
+            params.request_id.to_le_bytes().as_ref()
+        ],
+        bump = unstake_request.bump
+    )]
+    pub unstake_request: Account<'info, UnstakeRequest>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == stake_vault.token_mint,
+        constraint = user_token_account.owner == owner.key()
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.token_account_bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Treasury token account, or the reward token account of a stream paid in the
+    /// staked mint when penalties are redistributed to stakers
+    #[account(
+        mut,
+        constraint = penalty_token_account.mint == stake_vault.token_mint @ ErrorCode::InvalidPenaltyDestination
+    )]
+    pub penalty_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA used as transfer authority
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct InstantUnstakeParams {
+    pub request_id: u64,
+}
+
+pub fn handler(ctx: Context<InstantUnstake>, params: InstantUnstakeParams) -> Result<()> {
+    let user_stake = &mut ctx.accounts.user_stake;
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(
+        !stake_vault.is_paused,
+        ErrorCode::VaultPaused
+    );
+
+    require!(
+        stake_vault.permissions.allow_withdrawals,
+        ErrorCode::WithdrawalsDisabled
+    );
+
+    let unstake_request = &mut ctx.accounts.unstake_request;
+
+    let remaining_amount = unstake_request.remaining_amount();
+
+    require!(remaining_amount > 0, ErrorCode::NoClaimableAmount);
+
+    let penalty = unstake_request.early_exit_penalty(current_time, stake_vault.early_exit.penalty_bps);
+    let amount_released = remaining_amount
+        .checked_sub(penalty)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    unstake_request.claimed_amount = unstake_request.net_amount();
+
+    // Penalties go to the treasury, or back to stakers through a stake-mint reward stream
+    let penalty_token_account_key = ctx.accounts.penalty_token_account.key();
+    let penalty_stream_index = if penalty == 0 {
+        None
+    } else if stake_vault.early_exit.treasury == Pubkey::default() {
+        let stream_index = stake_vault
+            .reward_stream_index(&penalty_token_account_key)
+            .map_err(|_| ErrorCode::InvalidPenaltyDestination)?;
+        Some(stream_index)
+    } else {
+        require_keys_eq!(
+            ctx.accounts.penalty_token_account.owner,
+            stake_vault.early_exit.treasury,
+            ErrorCode::InvalidPenaltyDestination
+        );
+        None
+    };
+
+    let stake_vault_key = stake_vault.key();
+    let authority_seeds: &[&[&[u8]]] = &[&[
+        TRANSFER_AUTHORITY_SEED,
+        stake_vault_key.as_ref(),
+        &[stake_vault.transfer_authority_bump],
+    ]];
+
+    if amount_released > 0 {
+        transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.transfer_authority.to_account_info(),
+                },
+                authority_seeds,
+            ),
+            amount_released,
+            ctx.accounts.token_mint.decimals,
+        )?;
+    }
+
+    if penalty > 0 {
+        let penalty_received = transfer_checked_received(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.vault_token_account.to_account_info(),
+            &mut ctx.accounts.penalty_token_account,
+            &ctx.accounts.token_mint,
+            ctx.accounts.transfer_authority.to_account_info(),
+            authority_seeds,
+            penalty,
+        )?;
+
+        if let Some(stream_index) = penalty_stream_index {
+            // Stream what the reward account received over what is left of the running period, if any
+            stake_vault.update_reward_streams(current_time)?;
+            let reward_stream = &mut stake_vault.reward_streams[stream_index];
+            let duration = if reward_stream.period_finish > current_time {
+                (reward_stream.period_finish - current_time) as u64
+            } else {
+                PENALTY_REWARD_DURATION
+            };
+            reward_stream.notify_reward_amount(penalty_received, duration, current_time)?;
+        }
+    }
+
+    // Update vault stats - the whole remainder leaves the staked principal
+    stake_vault.stake_stats.unstaking_amount = stake_vault
+        .stake_stats
+        .unstaking_amount
+        .checked_sub(remaining_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.stake_stats.total_staked = stake_vault
+        .stake_stats
+        .total_staked
+        .checked_sub(remaining_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.stake_stats.total_vested = stake_vault
+        .stake_stats
+        .total_vested
+        .checked_add(amount_released)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Update user stake
+    user_stake.vested_stake_amount = user_stake
+        .vested_stake_amount
+        .checked_add(amount_released)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    user_stake.staked_amount = user_stake
+        .staked_amount
+        .checked_sub(remaining_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    user_stake.unstaking_amount = user_stake
+        .unstaking_amount
+        .checked_sub(remaining_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    user_stake.open_unstake_requests = user_stake
+        .open_unstake_requests
+        .checked_sub(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    user_stake.last_update_timestamp = current_time;
+
+    emit_cpi!(InstantUnstaked {
+        user: ctx.accounts.owner.key(),
+        request_id: params.request_id,
+        amount_released,
+        penalty,
+        penalty_destination: penalty_token_account_key,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
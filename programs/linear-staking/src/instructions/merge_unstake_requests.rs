@@ -0,0 +1,209 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::{STAKE_VAULT_SEED, UNSTAKE_REQUEST_SEED, USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::UnstakeRequestsMerged,
+    state::{StakeVault, UnstakeRequest, UserStake, VestingSchedule},
+    instructions::helpers::load_unstake_requests,
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+#[instruction(params: MergeUnstakeRequestsParams)]
+pub struct MergeUnstakeRequests<'info> {
+    /// Receives the rent of the merged-away requests
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(
+        mut,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    /// Request that survives the merge and keeps its id
+    #[account(
+        mut,
+        seeds = [
+            UNSTAKE_REQUEST_SEED,
+            user_stake.key().as_ref(),
+            user_stake.generation.to_le_bytes().as_ref(),
+            params.request_id.to_le_bytes().as_ref()
+        ],
+        bump = unstake_request.bump
+    )]
+    pub unstake_request: Account<'info, UnstakeRequest>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct MergeUnstakeRequestsParams {
+    pub request_id: u64,
+}
+
+/// Only linear requests past their cliff can be merged, whose claims are not ahead of
+/// what has vested. A cancel after claims leaves the claimable amount at zero for a
+/// while, then rising, which the merged schedule could unlock earlier. Otherwise the
+/// combined unlock curve is concave and stays above the single linear schedule that
+/// replaces them.
+fn is_mergeable(unstake_request: &UnstakeRequest, current_time: i64) -> bool {
+    unstake_request.vesting_schedule == VestingSchedule::Linear
+        && current_time >= unstake_request.cliff_time()
+        && unstake_request.claimed_amount <= unstake_request.vested_amount(current_time)
+}
+
+/// Merges the `UnstakeRequest` accounts passed as remaining accounts (writable) into
+/// `unstake_request`. The merged request keeps what is claimable right now and vests
+/// the rest linearly until the latest end time of the originals, so nothing unlocks
+/// earlier than it would have. Merged-away requests are closed.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, MergeUnstakeRequests<'info>>,
+    params: MergeUnstakeRequestsParams,
+) -> Result<()> {
+    let user_stake = &mut ctx.accounts.user_stake;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(
+        !ctx.accounts.stake_vault.is_paused,
+        ErrorCode::VaultPaused
+    );
+
+    let target_key = ctx.accounts.unstake_request.key();
+    let merged_requests = load_unstake_requests(ctx.remaining_accounts, &user_stake.key())?;
+
+    require!(
+        !merged_requests.is_empty()
+            && merged_requests.iter().all(|request| request.key() != target_key),
+        ErrorCode::UnstakeRequestsNotMergeable
+    );
+
+    let unstake_request = &mut ctx.accounts.unstake_request;
+    let mut remaining_amount: u64 = 0;
+    let mut claimable_amount: u64 = 0;
+    let mut end_time = i64::MIN;
+
+    for request in merged_requests.iter().map(|request| &**request).chain([&**unstake_request]) {
+        require!(
+            is_mergeable(request, current_time),
+            ErrorCode::UnstakeRequestsNotMergeable
+        );
+
+        remaining_amount = remaining_amount
+            .checked_add(request.remaining_amount())
+            .ok_or(ErrorCode::MathOverflow)?;
+        claimable_amount = claimable_amount
+            .checked_add(request.claimable_amount(current_time))
+            .ok_or(ErrorCode::MathOverflow)?;
+        end_time = end_time.max(request.end_time());
+    }
+
+    // Start the merged schedule early enough that the claimable amount carries over:
+    // remaining * (now - start) / (end - start) = claimable. Rounding moves the start
+    // later, which can only slow unlocking down.
+    let start_time = if claimable_amount < remaining_amount && end_time > current_time {
+        let head_start = (claimable_amount as u128 * (end_time - current_time) as u128)
+            / (remaining_amount - claimable_amount) as u128;
+
+        current_time
+            .checked_sub(i64::try_from(head_start).map_err(|_| ErrorCode::MathOverflow)?)
+            .ok_or(ErrorCode::MathOverflow)?
+    } else {
+        // Everything is vested already
+        end_time = current_time;
+        current_time
+    };
+
+    let merged_request_ids: Vec<u64> = merged_requests.iter().map(|request| request.id).collect();
+
+    unstake_request.total_amount = remaining_amount;
+    unstake_request.claimed_amount = 0;
+    unstake_request.cancelled_amount = 0;
+    unstake_request.start_time = start_time;
+    unstake_request.vesting_period_seconds = (end_time - start_time) as u64;
+    unstake_request.cliff_period_seconds = 0;
+
+    for request in merged_requests {
+        request.close(ctx.accounts.owner.to_account_info())?;
+    }
+
+    user_stake.open_unstake_requests = user_stake
+        .open_unstake_requests
+        .checked_sub(merged_request_ids.len() as u64)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    user_stake.last_update_timestamp = current_time;
+
+    emit_cpi!(UnstakeRequestsMerged {
+        user: ctx.accounts.owner.key(),
+        request_id: params.request_id,
+        merged_request_ids,
+        total_amount: remaining_amount,
+        start_time,
+        end_time,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear_request(total_amount: u64, start_time: i64, vesting_period_seconds: u64) -> UnstakeRequest {
+        UnstakeRequest {
+            user_stake: Pubkey::default(),
+            id: 0,
+            total_amount,
+            claimed_amount: 0,
+            cancelled_amount: 0,
+            start_time,
+            vesting_period_seconds,
+            cliff_period_seconds: 0,
+            vesting_schedule: VestingSchedule::Linear,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn requests_cancelled_after_claims_ahead_of_vesting_are_not_mergeable() {
+        // 500 of 1_000 claimed halfway through, then 400 cancelled: 600 left vesting,
+        // 300 of it vested, so nothing is claimable again until t=84
+        let mut cancelled = linear_request(1_000, 0, 100);
+        cancelled.claimed_amount = 500;
+        cancelled.cancelled_amount = 400;
+        assert_eq!(cancelled.claimable_amount(50), 0);
+        assert_eq!(cancelled.claimable_amount(83), 0);
+        assert!(!is_mergeable(&cancelled, 50));
+
+        // Merging it with a fresh request would have unlocked 66 by t=83 instead of 33
+        let fresh = linear_request(100, 50, 100);
+        assert!(is_mergeable(&fresh, 50));
+        assert_eq!(cancelled.claimable_amount(83) + fresh.claimable_amount(83), 33);
+
+        // Mergeable again once vesting catches up with the claims
+        assert!(is_mergeable(&cancelled, 84));
+    }
+
+    #[test]
+    fn requests_cancelled_without_running_ahead_stay_mergeable() {
+        let mut cancelled = linear_request(1_000, 0, 100);
+        cancelled.claimed_amount = 200;
+        cancelled.cancelled_amount = 400;
+        assert!(is_mergeable(&cancelled, 50));
+    }
+}
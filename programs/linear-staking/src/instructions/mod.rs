@@ -1,21 +1,47 @@
 pub mod initialize;
 pub mod deposit_stake;
+pub mod deposit_stake_for;
 pub mod unstake_request;
 pub mod claim_vested;
 pub mod cancel_unstake;
+pub mod instant_unstake;
+pub mod merge_unstake_requests;
+pub mod emergency_exit;
+pub mod emergency_redeem;
+pub mod close_user_stake;
+pub mod transfer_position;
 pub mod helpers;
 pub mod deposit_rewards;
 pub mod distribute_rewards;
 pub mod collect_rewards;
+pub mod compound_rewards;
+pub mod set_auto_compound;
+pub mod set_delegate;
+pub mod settle_liquid_rewards;
+pub mod checkpoint_voting_power;
+pub mod expire_stake_locks;
 pub mod admin_instructions;
 
 pub use initialize::*;
 pub use deposit_stake::*;
+pub use deposit_stake_for::*;
 pub use unstake_request::*;
 pub use claim_vested::*;
 pub use cancel_unstake::*;
+pub use instant_unstake::*;
+pub use merge_unstake_requests::*;
+pub use emergency_exit::*;
+pub use emergency_redeem::*;
+pub use close_user_stake::*;
+pub use transfer_position::*;
 pub use helpers::*;
 pub use deposit_rewards::*;
 pub use distribute_rewards::*;
 pub use collect_rewards::*;
+pub use compound_rewards::*;
+pub use set_auto_compound::*;
+pub use set_delegate::*;
+pub use settle_liquid_rewards::*;
+pub use checkpoint_voting_power::*;
+pub use expire_stake_locks::*;
 pub use admin_instructions::*;
\ No newline at end of file
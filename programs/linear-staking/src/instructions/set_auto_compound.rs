@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::{USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::AutoCompoundUpdated,
+    state::UserStake,
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct SetAutoCompound<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [USER_STAKE_SEED, user_stake.stake_vault.as_ref(), owner.key().as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SetAutoCompoundParams {
+    pub enabled: bool,
+}
+
+pub fn handler(ctx: Context<SetAutoCompound>, params: SetAutoCompoundParams) -> Result<()> {
+    let user_stake = &mut ctx.accounts.user_stake;
+    user_stake.auto_compound = params.enabled;
+
+    emit_cpi!(AutoCompoundUpdated {
+        user: user_stake.owner,
+        stake_vault: user_stake.stake_vault,
+        enabled: params.enabled,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::{USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::DelegateUpdated,
+    state::UserStake,
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [USER_STAKE_SEED, user_stake.stake_vault.as_ref(), owner.key().as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SetDelegateParams {
+    /// New delegate, None revokes the current one
+    pub delegate: Option<Pubkey>,
+}
+
+pub fn handler(ctx: Context<SetDelegate>, params: SetDelegateParams) -> Result<()> {
+    require!(
+        params.delegate != Some(ctx.accounts.owner.key()),
+        ErrorCode::InvalidDelegate
+    );
+
+    let user_stake = &mut ctx.accounts.user_stake;
+    user_stake.delegate = params.delegate;
+
+    emit_cpi!(DelegateUpdated {
+        user: user_stake.owner,
+        stake_vault: user_stake.stake_vault,
+        delegate: params.delegate,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
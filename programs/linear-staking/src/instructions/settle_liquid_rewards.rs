@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::{
+        STAKE_VAULT_SEED, STAKE_VAULT_TOKEN_ACCOUNT_SEED, REWARD_TOKEN_ACCOUNT_SEED, TRANSFER_AUTHORITY_SEED,
+        EVENT_AUTHORITY_SEED,
+    },
+    error::ErrorCode,
+    events::LiquidRewardsSettled,
+    state::StakeVault,
+    instructions::helpers::transfer_checked_received,
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct SettleLiquidRewards<'info> {
+    /// Anyone can crank this instruction
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(address = stake_vault.token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Token account of the reward stream paid in the staked mint
+    #[account(
+        mut,
+        seeds = [REWARD_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref(), stake_vault.token_mint.as_ref()],
+        bump
+    )]
+    pub reward_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.token_account_bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA used as transfer authority
+    #[account(
+        seeds = [TRANSFER_AUTHORITY_SEED, stake_vault.key().as_ref()],
+        bump = stake_vault.transfer_authority_bump
+    )]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+/// Rewards compounded into the liquid pool are only accounted for when streamed,
+/// this moves them from the reward stream's token account into the vault. Succeeds
+/// without moving anything when nothing is left to settle, so clients can always put
+/// it ahead of a receipt redemption in the same transaction.
+pub fn handler(ctx: Context<SettleLiquidRewards>) -> Result<()> {
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    // Receipts can only be redeemed in an emergency once the pool's rewards are settled
+    require!(
+        !stake_vault.is_paused || stake_vault.is_emergency,
+        ErrorCode::VaultPaused
+    );
+
+    stake_vault.update_reward_streams(current_time)?;
+
+    let amount = stake_vault.liquid_pool.uncollected_rewards;
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let stream_index = stake_vault.reward_stream_index(&ctx.accounts.reward_token_account.key())?;
+    let reward_stream = &mut stake_vault.reward_streams[stream_index];
+    reward_stream.total_claimed = reward_stream
+        .total_claimed
+        .checked_add(amount as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    stake_vault.liquid_pool.uncollected_rewards = 0;
+
+    let stake_vault_key = stake_vault.key();
+    let authority_seeds: &[&[&[u8]]] = &[&[
+        TRANSFER_AUTHORITY_SEED,
+        stake_vault_key.as_ref(),
+        &[stake_vault.transfer_authority_bump],
+    ]];
+
+    let amount_received = transfer_checked_received(
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.reward_token_account.to_account_info(),
+        &mut ctx.accounts.vault_token_account,
+        &ctx.accounts.token_mint,
+        ctx.accounts.transfer_authority.to_account_info(),
+        authority_seeds,
+        amount,
+    )?;
+
+    // The pool was credited the full amount when compounding, take back what transfer fees withheld
+    let shortfall = amount - amount_received;
+    if shortfall > 0 {
+        stake_vault.liquid_pool.pooled_amount = stake_vault
+            .liquid_pool
+            .pooled_amount
+            .checked_sub(shortfall)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        stake_vault.stake_stats.total_staked = stake_vault
+            .stake_stats
+            .total_staked
+            .checked_sub(shortfall)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        stake_vault.stake_stats.active_amount = stake_vault
+            .stake_stats
+            .active_amount
+            .checked_sub(shortfall)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    emit_cpi!(LiquidRewardsSettled {
+        stake_vault: stake_vault_key,
+        amount: amount_received,
+        pooled_amount: stake_vault.liquid_pool.pooled_amount,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
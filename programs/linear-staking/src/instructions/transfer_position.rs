@@ -0,0 +1,209 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::{STAKE_VAULT_SEED, USER_STAKE_SEED, VOTING_POWER_SEED, EVENT_AUTHORITY_SEED},
+    error::ErrorCode,
+    events::PositionTransferred,
+    state::{StakeVault, UnstakeRequest, UserStake, VotingPowerRecord},
+    instructions::helpers::{
+        create_unstake_request_account, initialize_user_stake, load_unstake_requests,
+        record_voting_power, refresh_user_rewards, settle_expired_stake_locks,
+        update_reward_snapshot_after_stake_change,
+    },
+    program::LinearStaking,
+};
+
+#[derive(Accounts)]
+pub struct TransferPosition<'info> {
+    /// Pays rent for the recipient's accounts and receives the rent of the moved requests
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Co-signs so a key rotation or OTC sale (with payment in the same transaction) is explicit
+    #[account(constraint = recipient.key() != owner.key() @ ErrorCode::InvalidTransferRecipient)]
+    pub recipient: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
+        bump = stake_vault.bump
+    )]
+    pub stake_vault: Account<'info, StakeVault>,
+
+    #[account(
+        mut,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
+        bump = user_stake.bump,
+        constraint = user_stake.owner == owner.key() @ ErrorCode::Unauthorized
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + UserStake::INIT_SPACE,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), recipient.key().as_ref()],
+        bump
+    )]
+    pub recipient_stake: Account<'info, UserStake>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, recipient_stake.key().as_ref()],
+        bump
+    )]
+    pub recipient_voting_power_record: Account<'info, VotingPowerRecord>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: event authority for emit_cpi
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: AccountInfo<'info>,
+
+    pub program: Program<'info, LinearStaking>,
+}
+
+/// Move the whole position: active stake with its lockups, unclaimed rewards and
+/// unstake requests. Every open request of the owner must be passed as a writable
+/// remaining account, followed by the recipient's PDAs for the request ids they
+/// will get under their generation, starting at their `unstake_request_count`, in the same order.
+pub fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, TransferPosition<'info>>) -> Result<()> {
+    let user_stake = &mut ctx.accounts.user_stake;
+    let recipient_stake = &mut ctx.accounts.recipient_stake;
+    let stake_vault = &mut ctx.accounts.stake_vault;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(
+        !stake_vault.is_paused,
+        ErrorCode::VaultPaused
+    );
+
+    let request_count = user_stake.open_unstake_requests as usize;
+    require!(
+        ctx.remaining_accounts.len() == request_count * 2,
+        ErrorCode::MissingUnstakeRequests
+    );
+
+    let (moved_request_infos, new_request_infos) = ctx.remaining_accounts.split_at(request_count);
+    let moved_requests = load_unstake_requests(moved_request_infos, &user_stake.key())?;
+
+    // Initialize the recipient's user stake if this is their first position
+    initialize_user_stake(
+        recipient_stake,
+        ctx.accounts.recipient.key(),
+        stake_vault,
+        ctx.bumps.recipient_stake,
+    )?;
+
+    // Stream rewards up to now, then refresh both users before moving stake
+    settle_expired_stake_locks(user_stake, stake_vault, current_time)?;
+    settle_expired_stake_locks(recipient_stake, stake_vault, current_time)?;
+    stake_vault.update_reward_streams(current_time)?;
+    refresh_user_rewards(user_stake, stake_vault)?;
+    refresh_user_rewards(recipient_stake, stake_vault)?;
+
+    let staked_amount = user_stake.staked_amount;
+
+    recipient_stake.staked_amount = recipient_stake
+        .staked_amount
+        .checked_add(staked_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    recipient_stake.active_stake_amount = recipient_stake
+        .active_stake_amount
+        .checked_add(user_stake.active_stake_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    recipient_stake.unstaking_amount = recipient_stake
+        .unstaking_amount
+        .checked_add(user_stake.unstaking_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Lockups keep their terms
+    for lock in user_stake.stake_locks.drain(..) {
+        recipient_stake.add_stake_lock(lock)?;
+    }
+
+    for (reward, recipient_reward) in user_stake
+        .reward_streams
+        .iter_mut()
+        .zip(recipient_stake.reward_streams.iter_mut())
+    {
+        recipient_reward.unclaimed_rewards = recipient_reward
+            .unclaimed_rewards
+            .checked_add(reward.unclaimed_rewards)
+            .ok_or(ErrorCode::MathOverflow)?;
+        reward.unclaimed_rewards = 0;
+    }
+
+    // Recreate each request under the recipient's user stake, keeping its schedule
+    for (moved_request, new_request_info) in moved_requests.into_iter().zip(new_request_infos) {
+        let id = recipient_stake.unstake_request_count;
+        recipient_stake.unstake_request_count = id
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        create_unstake_request_account(
+            UnstakeRequest {
+                user_stake: recipient_stake.key(),
+                id,
+                ..(*moved_request).clone()
+            },
+            recipient_stake.generation,
+            new_request_info,
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        moved_request.close(ctx.accounts.owner.to_account_info())?;
+    }
+
+    recipient_stake.open_unstake_requests = recipient_stake
+        .open_unstake_requests
+        .checked_add(request_count as u64)
+        .ok_or(ErrorCode::MathOverflow)?;
+    recipient_stake.last_update_timestamp = current_time;
+
+    user_stake.staked_amount = 0;
+    user_stake.active_stake_amount = 0;
+    user_stake.unstaking_amount = 0;
+    user_stake.open_unstake_requests = 0;
+    user_stake.last_update_timestamp = current_time;
+
+    // Carry the weighted stake over to the recipient
+    update_reward_snapshot_after_stake_change(user_stake, stake_vault, current_time)?;
+    update_reward_snapshot_after_stake_change(recipient_stake, stake_vault, current_time)?;
+
+    // Voting power moves along with the stake
+    record_voting_power(
+        &mut ctx.accounts.voting_power_record,
+        user_stake,
+        stake_vault,
+        ctx.bumps.voting_power_record,
+    )?;
+    record_voting_power(
+        &mut ctx.accounts.recipient_voting_power_record,
+        recipient_stake,
+        stake_vault,
+        ctx.bumps.recipient_voting_power_record,
+    )?;
+
+    emit_cpi!(PositionTransferred {
+        from: ctx.accounts.owner.key(),
+        to: ctx.accounts.recipient.key(),
+        staked_amount,
+        unstake_requests: request_count as u64,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
@@ -1,11 +1,15 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{burn, Burn, Mint, TokenAccount, TokenInterface};
 
 use crate::{
-    constants::{STAKE_VAULT_SEED, USER_STAKE_SEED, EVENT_AUTHORITY_SEED},
+    constants::{STAKE_VAULT_SEED, UNSTAKE_REQUEST_SEED, USER_STAKE_SEED, VOTING_POWER_SEED, EVENT_AUTHORITY_SEED},
     error::ErrorCode,
     events::UnstakeRequested,
-    state::{user_stake::MAX_UNSTAKE_REQUESTS, StakeVault, UnstakeRequest, UserStake},
-    instructions::helpers::{refresh_user_rewards, update_reward_snapshot_after_stake_change},
+    state::{StakeVault, UnstakeRequest, UserStake, VotingPowerRecord},
+    instructions::helpers::{
+        initialize_user_stake, record_voting_power, refresh_user_rewards, settle_expired_stake_locks,
+        update_reward_snapshot_after_stake_change,
+    },
     program::LinearStaking,
 };
 
@@ -16,18 +20,59 @@ pub struct UnstakeRequestCtx<'info> {
 
     #[account(
         mut,
-        seeds = [STAKE_VAULT_SEED],
+        seeds = [STAKE_VAULT_SEED, stake_vault.token_mint.as_ref()],
         bump
     )]
     pub stake_vault: Account<'info, StakeVault>,
 
+    /// Created when redeeming receipts without a position of one's own
     #[account(
-        mut,
-        seeds = [USER_STAKE_SEED, owner.key().as_ref()],
+        init_if_needed,
+        payer = owner,
+        space = 8 + UserStake::INIT_SPACE,
+        seeds = [USER_STAKE_SEED, stake_vault.key().as_ref(), owner.key().as_ref()],
         bump
     )]
     pub user_stake: Account<'info, UserStake>,
 
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VotingPowerRecord::INIT_SPACE,
+        seeds = [VOTING_POWER_SEED, user_stake.key().as_ref()],
+        bump
+    )]
+    pub voting_power_record: Account<'info, VotingPowerRecord>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + UnstakeRequest::INIT_SPACE,
+        seeds = [
+            UNSTAKE_REQUEST_SEED,
+            user_stake.key().as_ref(),
+            user_stake.generation.to_le_bytes().as_ref(),
+            user_stake.unstake_request_count.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub unstake_request: Account<'info, UnstakeRequest>,
+
+    /// Passing the receipt accounts redeems liquid stake by burning receipts
+    #[account(mut, address = stake_vault.liquid_pool.receipt_mint)]
+    pub receipt_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = stake_vault.liquid_pool.receipt_mint,
+        token::authority = owner
+    )]
+    pub user_receipt_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub system_program: Program<'info, System>,
+
     /// CHECK: event authority for emit_cpi
     #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
     pub event_authority: AccountInfo<'info>,
@@ -37,6 +82,7 @@ pub struct UnstakeRequestCtx<'info> {
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct UnstakeRequestParams {
+    /// Amount of active stake, or of receipts to burn when redeeming liquid stake
     pub amount: u64,
 }
 
@@ -55,30 +101,107 @@ pub fn handler(ctx: Context<UnstakeRequestCtx>, params: UnstakeRequestParams) ->
         ErrorCode::VaultPaused
     );
 
-    require!(
-        params.amount > 0 && params.amount <= user_stake.active_stake_amount,
-        ErrorCode::InvalidAmount
-    );
+    require!(params.amount > 0, ErrorCode::InvalidAmount);
 
-    require!(
-        user_stake.unstake_requests.len() < MAX_UNSTAKE_REQUESTS,
-        ErrorCode::MaxUnstakeRequestsReached
-    );
+    initialize_user_stake(user_stake, ctx.accounts.owner.key(), stake_vault, ctx.bumps.user_stake)?;
 
-    // Refresh user rewards before changing stake
+    // Stream rewards up to now, then refresh user rewards before changing stake
+    settle_expired_stake_locks(user_stake, stake_vault, current_time)?;
+    stake_vault.update_reward_streams(current_time)?;
     refresh_user_rewards(user_stake, stake_vault)?;
 
+    let amount = if let Some(receipt_mint) = &ctx.accounts.receipt_mint {
+        let (Some(user_receipt_token_account), Some(token_program)) = (
+            &ctx.accounts.user_receipt_token_account,
+            &ctx.accounts.token_program,
+        ) else {
+            return Err(ErrorCode::MissingReceiptAccounts.into());
+        };
+
+        // The exchange rate includes rewards compounded up to now, which must be in the
+        // vault before they can be paid out, otherwise they come out of other stakers' principal
+        require!(
+            stake_vault.liquid_pool.uncollected_rewards == 0,
+            ErrorCode::LiquidRewardsNotSettled
+        );
+
+        let amount = stake_vault
+            .liquid_pool
+            .amount_for_receipts(params.amount, receipt_mint.supply)?;
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        burn(
+            CpiContext::new(
+                token_program.to_account_info(),
+                Burn {
+                    mint: receipt_mint.to_account_info(),
+                    from: user_receipt_token_account.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            params.amount,
+        )?;
+
+        stake_vault.liquid_pool.pooled_amount = stake_vault
+            .liquid_pool
+            .pooled_amount
+            .checked_sub(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // The redeemed stake vests out through the user's own position
+        user_stake.staked_amount = user_stake
+            .staked_amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        amount
+    } else {
+        require!(
+            params.amount <= user_stake.active_stake_amount,
+            ErrorCode::InvalidAmount
+        );
+
+        require!(
+            params.amount <= user_stake.active_stake_amount.saturating_sub(user_stake.get_locked_amount(current_time)),
+            ErrorCode::StakeLocked
+        );
+
+        user_stake.active_stake_amount = user_stake
+            .active_stake_amount
+            .checked_sub(params.amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        params.amount
+    };
+
     // Create new unstake request
-    user_stake.unstake_requests.push(UnstakeRequest {
-        total_amount: params.amount,
+    let request_id = user_stake.unstake_request_count;
+    user_stake.unstake_request_count = request_id
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    ctx.accounts.unstake_request.set_inner(UnstakeRequest {
+        user_stake: user_stake.key(),
+        id: request_id,
+        total_amount: amount,
         claimed_amount: 0,
+        cancelled_amount: 0,
         start_time: current_time,
+        vesting_period_seconds: stake_vault.vesting_period_seconds,
+        cliff_period_seconds: stake_vault.cliff_period_seconds,
+        vesting_schedule: stake_vault.vesting_schedule,
+        bump: ctx.bumps.unstake_request,
     });
 
     // Update user stake amounts
-    user_stake.active_stake_amount = user_stake
-        .active_stake_amount
-        .checked_sub(params.amount)
+    user_stake.unstaking_amount = user_stake
+        .unstaking_amount
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    user_stake.open_unstake_requests = user_stake
+        .open_unstake_requests
+        .checked_add(1)
         .ok_or(ErrorCode::MathOverflow)?;
 
     user_stake.last_update_timestamp = current_time;
@@ -87,13 +210,13 @@ pub fn handler(ctx: Context<UnstakeRequestCtx>, params: UnstakeRequestParams) ->
     stake_vault.stake_stats.active_amount = stake_vault
         .stake_stats
         .active_amount
-        .checked_sub(params.amount)
+        .checked_sub(amount)
         .ok_or(ErrorCode::MathOverflow)?;
 
     stake_vault.stake_stats.unstaking_amount = stake_vault
         .stake_stats
         .unstaking_amount
-        .checked_add(params.amount)
+        .checked_add(amount)
         .ok_or(ErrorCode::MathOverflow)?;
 
     stake_vault.collective_unstake_requests_count = stake_vault
@@ -101,17 +224,26 @@ pub fn handler(ctx: Context<UnstakeRequestCtx>, params: UnstakeRequestParams) ->
         .checked_add(1)
         .ok_or(ErrorCode::MathOverflow)?;
 
-    // Update reward snapshot after stake change
-    update_reward_snapshot_after_stake_change(user_stake, stake_vault)?;
+    // Update weighted stake and reward snapshot after stake change
+    update_reward_snapshot_after_stake_change(user_stake, stake_vault, current_time)?;
+    record_voting_power(
+        &mut ctx.accounts.voting_power_record,
+        user_stake,
+        stake_vault,
+        ctx.bumps.voting_power_record,
+    )?;
 
-    let end_time = current_time + stake_vault.vesting_period_seconds as i64;
+    let cliff_time = ctx.accounts.unstake_request.cliff_time();
+    let end_time = ctx.accounts.unstake_request.end_time();
 
     emit_cpi!(UnstakeRequested {
         user: ctx.accounts.owner.key(),
-        amount: params.amount,
-        request_index: user_stake.unstake_requests.len() as u64 - 1,
+        amount,
+        request_id,
         start_time: current_time,
+        cliff_time,
         end_time,
+        vesting_schedule: stake_vault.vesting_schedule,
     });
 
     Ok(())
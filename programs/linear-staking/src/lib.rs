@@ -1,3 +1,12 @@
+//! Linear-vesting staking vaults with streamed rewards.
+//!
+//! Accounts of the first release are not migrated. Unstake requests now snapshot their
+//! vesting schedule and live in their own accounts, and the PDA seeds and the layouts of
+//! `StakeVault`, `UserStake` and `UnstakeRequest` changed with them. This version is
+//! deployed fresh under a new program id, the first release stays at
+//! `8Q7GV6Hw2x1FRvV1TUG5LQvUea82HuS2HK297q7LzWDv`: vaults are initialized again, and
+//! users withdraw from the old program and deposit into the new one.
+
 #![allow(ambiguous_glob_reexports)]
 
 pub mod constants;
@@ -13,13 +22,13 @@ pub use events::*;
 pub use instructions::*;
 pub use state::*;
 
-declare_id!("8Q7GV6Hw2x1FRvV1TUG5LQvUea82HuS2HK297q7LzWDv");
+declare_id!("5coSkwNDycyBcYFkBidcUAQRwyuiPSxfKL3SH1nzuU3a");
 
 #[program]
 pub mod linear_staking {
     use super::*;
 
-    /// Initialize the stake vault with a token mint and optional custom vesting period
+    /// Initialize the stake vault with a token mint, reward mint and optional custom vesting period
     pub fn initialize(ctx: Context<Initialize>, params: InitializeParams) -> Result<()> {
         initialize::handler(ctx, params) 
     }
@@ -29,14 +38,23 @@ pub mod linear_staking {
         deposit_stake::handler(ctx, params)
     }
 
+    /// Stake the payer's tokens on behalf of a beneficiary, who alone controls the stake
+    pub fn deposit_stake_for(ctx: Context<DepositStakeFor>, params: DepositStakeForParams) -> Result<()> {
+        deposit_stake_for::handler(ctx, params)
+    }
+
     /// Request to unstake tokens - starts the linear vesting period
     pub fn unstake_request(ctx: Context<UnstakeRequestCtx>, params: UnstakeRequestParams) -> Result<()> {
         unstake_request::handler(ctx, params)
     }
 
-    /// Claim vested (unlocked) tokens from unstake requests
-    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
-        claim_vested::handler(ctx)
+    /// Claim vested (unlocked) tokens from the passed unstake requests, optionally capped.
+    /// Callable by the owner or their delegate, tokens always go to the owner
+    pub fn claim_vested<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimVested<'info>>,
+        params: ClaimVestedParams,
+    ) -> Result<()> {
+        claim_vested::handler(ctx, params)
     }
 
     /// Cancel an unstake request and return remaining tokens to active stake
@@ -44,21 +62,89 @@ pub mod linear_staking {
         cancel_unstake::handler(ctx, params)
     }
 
-    /// Admin deposits reward tokens into the vault
+    /// Release the rest of an unstake request right away, minus the early-exit penalty
+    pub fn instant_unstake(ctx: Context<InstantUnstake>, params: InstantUnstakeParams) -> Result<()> {
+        instant_unstake::handler(ctx, params)
+    }
+
+    /// Merge several unstake requests into one without unlocking anything earlier
+    pub fn merge_unstake_requests<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MergeUnstakeRequests<'info>>,
+        params: MergeUnstakeRequestsParams,
+    ) -> Result<()> {
+        merge_unstake_requests::handler(ctx, params)
+    }
+
+    /// Withdraw the full stake, including unvested requests, once the vault is in emergency mode
+    pub fn emergency_exit<'info>(ctx: Context<'_, '_, 'info, 'info, EmergencyExit<'info>>) -> Result<()> {
+        emergency_exit::handler(ctx)
+    }
+
+    /// Burn liquid staking receipts for their share of the pool once the vault is in emergency mode
+    pub fn emergency_redeem(ctx: Context<EmergencyRedeem>, params: EmergencyRedeemParams) -> Result<()> {
+        emergency_redeem::handler(ctx, params)
+    }
+
+    /// Move the whole position to another wallet, co-signed by the recipient
+    pub fn transfer_position<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TransferPosition<'info>>,
+    ) -> Result<()> {
+        transfer_position::handler(ctx)
+    }
+
+    /// Close an emptied user stake and its voting power record, returning their rent to the owner
+    pub fn close_user_stake(ctx: Context<CloseUserStake>) -> Result<()> {
+        close_user_stake::handler(ctx)
+    }
+
+    /// Reward depositor deposits reward tokens into the vault, streamed linearly over a duration
     pub fn deposit_rewards(ctx: Context<DepositRewards>, params: DepositRewardsParams) -> Result<()> {
         deposit_rewards::handler(ctx, params)
     }
 
-    /// Distribute pending rewards to the global accumulator (permissionless crank)
+    /// Stream rewards emitted so far into the global accumulators (permissionless crank)
     pub fn distribute_rewards(ctx: Context<DistributeRewards>) -> Result<()> {
         distribute_rewards::handler(ctx)
     }
 
-    /// User collects their accumulated rewards
-    pub fn collect_rewards(ctx: Context<CollectRewards>) -> Result<()> {
+    /// Collect accumulated rewards from one or more reward streams, by the owner or their delegate
+    pub fn collect_rewards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CollectRewards<'info>>,
+    ) -> Result<()> {
         collect_rewards::handler(ctx)
     }
 
+    /// Restake unclaimed staked-mint rewards, by the owner, their delegate, or anyone if the owner opted in
+    pub fn compound_rewards(ctx: Context<CompoundRewards>) -> Result<()> {
+        compound_rewards::handler(ctx)
+    }
+
+    /// Opt in or out of permissionless compounding of the user's rewards
+    pub fn set_auto_compound(ctx: Context<SetAutoCompound>, params: SetAutoCompoundParams) -> Result<()> {
+        set_auto_compound::handler(ctx, params)
+    }
+
+    /// Set or revoke the wallet allowed to claim and compound on the owner's behalf
+    pub fn set_delegate(ctx: Context<SetDelegate>, params: SetDelegateParams) -> Result<()> {
+        set_delegate::handler(ctx, params)
+    }
+
+    /// Move rewards compounded into the liquid pool into the vault token account (permissionless, also in emergency mode),
+    /// receipts are only redeemed once nothing is left to settle, so clients settle in the same transaction
+    pub fn settle_liquid_rewards(ctx: Context<SettleLiquidRewards>) -> Result<()> {
+        settle_liquid_rewards::handler(ctx)
+    }
+
+    /// Record the owner's current voting power for governance
+    pub fn checkpoint_voting_power(ctx: Context<CheckpointVotingPower>) -> Result<()> {
+        checkpoint_voting_power::handler(ctx)
+    }
+
+    /// Drop a user's expired lockup boosts from the vault's reward weight (permissionless)
+    pub fn expire_stake_locks(ctx: Context<ExpireStakeLocks>) -> Result<()> {
+        expire_stake_locks::handler(ctx)
+    }
+
     // ========================================================================
     // Admin Instructions
     // ========================================================================
@@ -73,7 +159,7 @@ pub mod linear_staking {
         admin_instructions::unpause_handler(ctx)
     }
 
-    /// Update the vesting period for unstake requests
+    /// Update the vesting period for new unstake requests
     pub fn update_vesting_period(
         ctx: Context<UpdateVestingPeriod>,
         params: UpdateVestingPeriodParams,
@@ -81,14 +167,56 @@ pub mod linear_staking {
         admin_instructions::update_vesting_period_handler(ctx, params)
     }
 
-    /// Emergency withdraw tokens from the vault (requires paused state)
+    /// Update the cliff for new unstake requests
+    pub fn update_cliff_period(
+        ctx: Context<UpdateCliffPeriod>,
+        params: UpdateCliffPeriodParams,
+    ) -> Result<()> {
+        admin_instructions::update_cliff_period_handler(ctx, params)
+    }
+
+    /// Select the unlock curve for new unstake requests
+    pub fn update_vesting_schedule(
+        ctx: Context<UpdateVestingSchedule>,
+        params: UpdateVestingScheduleParams,
+    ) -> Result<()> {
+        admin_instructions::update_vesting_schedule_handler(ctx, params)
+    }
+
+    /// Update the early-exit penalty and where penalties are sent
+    pub fn update_early_exit_config(
+        ctx: Context<UpdateEarlyExitConfig>,
+        params: UpdateEarlyExitConfigParams,
+    ) -> Result<()> {
+        admin_instructions::update_early_exit_config_handler(ctx, params)
+    }
+
+    /// Set the earliest slot governance reads voting power at, history from there on is never dropped
+    pub fn update_voting_power_retention(
+        ctx: Context<UpdateVotingPowerRetention>,
+        params: UpdateVotingPowerRetentionParams,
+    ) -> Result<()> {
+        admin_instructions::update_voting_power_retention_handler(ctx, params)
+    }
+
+    /// Put a paused vault into emergency mode for good, letting users exit with their full stake
+    pub fn enable_emergency_mode(ctx: Context<EnableEmergencyMode>) -> Result<()> {
+        admin_instructions::enable_emergency_mode_handler(ctx)
+    }
+
+    /// Withdraw tokens in excess of what is owed to users (requires emergency mode)
     pub fn emergency_withdraw(
-        ctx: Context<EmergencyWithdrawCtx>,
-        params: EmergencyWithdrawParams,
+        ctx: Context<WithdrawSurplus>,
+        params: WithdrawSurplusParams,
     ) -> Result<()> {
         admin_instructions::emergency_withdraw_handler(ctx, params)
     }
 
+    /// Withdraw tokens held by the vault in excess of what is owed to users, e.g. stray tokens
+    pub fn sweep_surplus(ctx: Context<WithdrawSurplus>, params: WithdrawSurplusParams) -> Result<()> {
+        admin_instructions::sweep_surplus_handler(ctx, params)
+    }
+
     /// Update deposit and withdrawal permissions
     pub fn update_permissions(
         ctx: Context<UpdatePermissions>,
@@ -96,4 +224,29 @@ pub mod linear_staking {
     ) -> Result<()> {
         admin_instructions::update_permissions_handler(ctx, params)
     }
+
+    /// Register an additional reward stream paid in another mint
+    pub fn add_reward_stream(ctx: Context<AddRewardStream>) -> Result<()> {
+        admin_instructions::add_reward_stream_handler(ctx)
+    }
+
+    /// Create the receipt mint so stake can be deposited into the liquid pool
+    pub fn enable_liquid_staking(ctx: Context<EnableLiquidStaking>) -> Result<()> {
+        admin_instructions::enable_liquid_staking_handler(ctx)
+    }
+
+    /// Propose a new admin, who takes over once they call accept_admin
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, params: ProposeAdminParams) -> Result<()> {
+        admin_instructions::propose_admin_handler(ctx, params)
+    }
+
+    /// Accept a pending admin proposal
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        admin_instructions::accept_admin_handler(ctx)
+    }
+
+    /// Assign the pauser, reward depositor, config manager and emergency guardian roles
+    pub fn update_roles(ctx: Context<UpdateRoles>, params: UpdateRolesParams) -> Result<()> {
+        admin_instructions::update_roles_handler(ctx, params)
+    }
 }
@@ -1,5 +1,11 @@
+pub mod stake_lock;
 pub mod stake_vault;
 pub mod user_stake;
+pub mod vesting_schedule;
+pub mod voting_power;
 
+pub use stake_lock::*;
 pub use stake_vault::*;
-pub use user_stake::*;
\ No newline at end of file
+pub use user_stake::*;
+pub use vesting_schedule::*;
+pub use voting_power::*;
\ No newline at end of file
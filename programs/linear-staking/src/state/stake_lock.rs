@@ -0,0 +1,69 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::{
+    BPS_DENOMINATOR, SIX_MONTH_LOCKUP_MULTIPLIER_BPS, THREE_MONTH_LOCKUP_MULTIPLIER_BPS,
+    TWELVE_MONTH_LOCKUP_MULTIPLIER_BPS,
+};
+
+pub const MAX_STAKE_LOCKS: usize = 16;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Fixed lockup a user can opt into on deposit in exchange for a reward weight multiplier
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum LockupTier {
+    ThreeMonths,
+    SixMonths,
+    TwelveMonths,
+}
+
+impl LockupTier {
+    pub fn duration(&self) -> i64 {
+        match self {
+            LockupTier::ThreeMonths => 90 * DAY,
+            LockupTier::SixMonths => 180 * DAY,
+            LockupTier::TwelveMonths => 365 * DAY,
+        }
+    }
+
+    pub fn multiplier_bps(&self) -> u16 {
+        match self {
+            LockupTier::ThreeMonths => THREE_MONTH_LOCKUP_MULTIPLIER_BPS,
+            LockupTier::SixMonths => SIX_MONTH_LOCKUP_MULTIPLIER_BPS,
+            LockupTier::TwelveMonths => TWELVE_MONTH_LOCKUP_MULTIPLIER_BPS,
+        }
+    }
+}
+
+/// Part of a user's active stake that cannot be unstaked before `unlock_time`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace, Default)]
+pub struct StakeLock {
+    pub amount: u64,
+    pub start_time: i64,
+    pub unlock_time: i64,
+    pub multiplier_bps: u16,
+}
+
+impl StakeLock {
+    pub fn is_expired(&self, current_time: i64) -> bool {
+        current_time >= self.unlock_time
+    }
+
+    /// Reward weight on top of the locked amount itself
+    pub fn bonus_weight(&self) -> u64 {
+        let bonus_bps = (self.multiplier_bps as u64).saturating_sub(BPS_DENOMINATOR);
+
+        (self.amount as u128 * bonus_bps as u128 / BPS_DENOMINATOR as u128) as u64
+    }
+
+    /// Bonus weight decayed linearly with the time left on the lock, vote-escrow style
+    pub fn decayed_bonus_weight(&self, current_time: i64) -> u64 {
+        let lock_duration = self.unlock_time.saturating_sub(self.start_time);
+        let remaining_time = self.unlock_time.saturating_sub(current_time.max(self.start_time));
+        if lock_duration <= 0 || remaining_time <= 0 {
+            return 0;
+        }
+
+        (self.bonus_weight() as u128 * remaining_time as u128 / lock_duration as u128) as u64
+    }
+}
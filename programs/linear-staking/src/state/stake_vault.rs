@@ -1,6 +1,9 @@
 use anchor_lang::prelude::*;
 
-pub const MAX_UNSTAKE_REQUESTS: usize = 32;
+use crate::{constants::PRECISION, error::ErrorCode, state::VestingSchedule};
+
+/// Maximum number of concurrent reward streams per vault
+pub const MAX_REWARD_STREAMS: usize = 4;
 
 #[account]
 #[derive(Debug, InitSpace)]
@@ -16,10 +19,165 @@ pub struct StakeVault {
     pub permissions: StakePermissions,
     pub vesting_period_seconds: u64, // no end time
     pub stake_stats: StakeStats,
-    pub reward_state: RewardState,
+    #[max_len(MAX_REWARD_STREAMS)]
+    pub reward_streams: Vec<RewardState>,
     pub start_time: i64,
     pub collective_unstake_requests_count: u64,
-    pub padding: [u8; 8],
+    pub cliff_period_seconds: u64, // nothing unlocks before the cliff, must not exceed vesting_period_seconds
+    pub vesting_schedule: VestingSchedule, // unlock curve copied onto new unstake requests
+    pub early_exit: EarlyExitConfig,
+    pub pending_admin: Pubkey, // must call accept_admin to take over, Pubkey::default() when none
+    pub roles: VaultRoles,
+    pub is_emergency: bool, // one-way: users exit with their full stake, only surplus can be withdrawn
+    pub liquid_pool: LiquidPool, // stake held for receipt token holders instead of a UserStake
+    pub voting_power_retention_slot: Option<u64>, // voting power history from this slot on is never dropped, only merged when full
+    pub user_stakes_created: u64, // next UserStake::generation, never reset
+}
+
+impl StakeVault {
+    /// Find the reward stream paying out of the given reward token account
+    pub fn reward_stream_index(&self, reward_token_account: &Pubkey) -> Result<usize> {
+        self.reward_streams
+            .iter()
+            .position(|stream| stream.token_account == *reward_token_account)
+            .ok_or(ErrorCode::InvalidRewardStream.into())
+    }
+
+    /// Tokens `token_account` must keep to honor users: staked principal for the vault
+    /// token account, outstanding rewards for a reward stream's token account.
+    /// Other accounts, e.g. stray tokens sent to the transfer authority, owe nothing.
+    pub fn token_account_obligation(&self, token_account: &Pubkey) -> Result<u64> {
+        if *token_account == self.vault_token_account {
+            return Ok(self.stake_stats.total_staked);
+        }
+
+        let Ok(stream_index) = self.reward_stream_index(token_account) else {
+            return Ok(0);
+        };
+        let outstanding_rewards = self.reward_streams[stream_index].outstanding_rewards()?;
+
+        u64::try_from(outstanding_rewards).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Time the reward streams were last updated to
+    pub fn last_reward_update_time(&self) -> i64 {
+        self.reward_streams
+            .iter()
+            .map(|stream| stream.last_update_time)
+            .max()
+            .unwrap_or(i64::MIN)
+    }
+
+    /// Liquid staking is enabled once the receipt mint exists
+    pub fn is_liquid(&self) -> bool {
+        self.liquid_pool.receipt_mint != Pubkey::default()
+    }
+
+    /// Reward stream paid in the staked mint, the only one the liquid pool earns from
+    pub fn compounding_stream_index(&self) -> Option<usize> {
+        self.reward_streams
+            .iter()
+            .position(|stream| stream.mint == self.token_mint)
+    }
+
+    /// Stream every reward stream up to `current_time` into its accumulator and
+    /// compound the liquid pool's share into its stake.
+    /// Returns the amount distributed by each stream.
+    /// Must be called BEFORE any change to the active stake or reward schedule.
+    pub fn update_reward_streams(&mut self, current_time: i64) -> Result<Vec<u128>> {
+        let total_weighted_stake = self.stake_stats.total_weighted_stake;
+        let compounding_stream_index = self.compounding_stream_index();
+
+        let mut distributed = Vec::with_capacity(self.reward_streams.len());
+        for (index, reward_stream) in self.reward_streams.iter_mut().enumerate() {
+            let stream_weight = if Some(index) == compounding_stream_index {
+                total_weighted_stake
+                    .checked_add(self.liquid_pool.pooled_amount)
+                    .ok_or(ErrorCode::MathOverflow)?
+            } else {
+                total_weighted_stake
+            };
+
+            distributed.push(reward_stream.update(stream_weight, current_time)?);
+        }
+
+        if let Some(index) = compounding_stream_index {
+            let reward_per_token_staked = self.reward_streams[index].reward_per_token_staked;
+            let compounded = self.liquid_pool.compound(reward_per_token_staked)?;
+
+            self.stake_stats.total_staked = self
+                .stake_stats
+                .total_staked
+                .checked_add(compounded)
+                .ok_or(ErrorCode::MathOverflow)?;
+
+            self.stake_stats.active_amount = self
+                .stake_stats
+                .active_amount
+                .checked_add(compounded)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        Ok(distributed)
+    }
+}
+
+/// Pooled stake backing the receipt token. Holders own a share of `pooled_amount`
+/// proportional to their receipts, so the exchange rate grows as rewards compound.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace, Default)]
+pub struct LiquidPool {
+    /// Pubkey::default() until liquid staking is enabled
+    pub receipt_mint: Pubkey,
+    pub receipt_mint_bump: u8,
+    /// Staked tokens backing the receipt supply, including compounded rewards
+    pub pooled_amount: u64,
+    /// Accumulator of the compounding stream at the last compound (scaled by PRECISION)
+    pub reward_snapshot: u128,
+    /// Compounded rewards still held by the compounding stream's token account
+    pub uncollected_rewards: u64,
+}
+
+impl LiquidPool {
+    /// Add the pool's rewards since the last snapshot to its stake.
+    /// Returns the amount compounded.
+    pub fn compound(&mut self, reward_per_token_staked: u128) -> Result<u64> {
+        let earned = (self.pooled_amount as u128)
+            .checked_mul(reward_per_token_staked.saturating_sub(self.reward_snapshot))
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(PRECISION)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let earned = u64::try_from(earned).map_err(|_| ErrorCode::MathOverflow)?;
+
+        self.reward_snapshot = reward_per_token_staked;
+        self.pooled_amount = self
+            .pooled_amount
+            .checked_add(earned)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.uncollected_rewards = self
+            .uncollected_rewards
+            .checked_add(earned)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(earned)
+    }
+
+    /// Receipts minted for depositing `amount` at the current exchange rate
+    pub fn receipts_for_amount(&self, amount: u64, receipt_supply: u64) -> Result<u64> {
+        if receipt_supply == 0 || self.pooled_amount == 0 {
+            return Ok(amount);
+        }
+
+        let receipts = amount as u128 * receipt_supply as u128 / self.pooled_amount as u128;
+        u64::try_from(receipts).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Staked tokens redeemed by burning `receipts` at the current exchange rate
+    pub fn amount_for_receipts(&self, receipts: u64, receipt_supply: u64) -> Result<u64> {
+        require!(receipt_supply > 0, ErrorCode::InsufficientBalance);
+
+        let amount = receipts as u128 * self.pooled_amount as u128 / receipt_supply as u128;
+        u64::try_from(amount).map_err(|_| ErrorCode::MathOverflow.into())
+    }
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace, Default)]
@@ -28,6 +186,30 @@ pub struct StakePermissions {
     pub allow_withdrawals: bool,
 }
 
+/// Keys allowed to run each class of privileged instruction.
+/// The admin only transfers adminship and assigns these roles.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace, Default)]
+pub struct VaultRoles {
+    /// Pauses and unpauses the vault
+    pub pauser: Pubkey,
+    /// Funds reward streams
+    pub reward_depositor: Pubkey,
+    /// Changes vesting, early-exit, permission and reward stream settings
+    pub config_manager: Pubkey,
+    /// Withdraws surplus funds, including everything but user funds in an emergency
+    pub emergency_guardian: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace, Default)]
+pub struct EarlyExitConfig {
+    /// Penalty on the unvested amount of a request released right after it was created,
+    /// decaying linearly to zero at the end of its vesting period
+    pub penalty_bps: u16,
+    /// Owner of the token account receiving penalties.
+    /// Pubkey::default() redistributes them to stakers through a reward stream paid in the staked mint.
+    pub treasury: Pubkey,
+}
+
 #[account]
 #[derive(Debug, InitSpace, Default)]
 pub struct StakeStats {
@@ -35,17 +217,133 @@ pub struct StakeStats {
     pub active_amount: u64,     // total tokens staked currently (earning rewards)
     pub unstaking_amount: u64,  // total tokens in linear vesting (not earning rewards)
     pub total_vested: u64,      // total tokens claimed from linear vesting (cumulative)
+    pub total_weighted_stake: u64, // active stake including lockup boosts (reward weight)
 }
 
 #[account]
 #[derive(Debug, InitSpace, Default)]
 pub struct RewardState {
-    /// Rewards deposited but not yet distributed to the accumulator
+    /// Mint rewards of this stream are paid in
+    pub mint: Pubkey,
+    /// Token account holding this stream's reward funds, kept apart from staked principal
+    pub token_account: Pubkey,
+    pub token_account_bump: u8,
+    /// Rewards deposited but not yet streamed into the accumulator
     pub pending_rewards: u128,
-    /// Cumulative rewards per token staked (scaled by PRECISION)
+    /// Cumulative rewards per weighted token staked (scaled by PRECISION)
     pub reward_per_token_staked: u128,
     /// Total rewards that have been distributed to the accumulator
     pub total_distributed: u128,
     /// Total rewards that have been claimed by users
     pub total_claimed: u128,
+    /// Rewards emitted per second (scaled by PRECISION)
+    pub reward_rate: u128,
+    /// Timestamp at which the current reward period ends
+    pub period_finish: i64,
+    /// Timestamp up to which rewards have been streamed into the accumulator
+    pub last_update_time: i64,
+}
+
+impl RewardState {
+    /// Stream rewards emitted since `last_update_time` into the accumulator.
+    /// Returns the amount of rewards that were distributed.
+    pub fn update(&mut self, total_weighted_stake: u64, current_time: i64) -> Result<u128> {
+        let last_update_time = self.last_update_time;
+        let applicable_time = current_time.min(self.period_finish);
+
+        self.last_update_time = current_time;
+
+        if applicable_time <= last_update_time || self.pending_rewards == 0 {
+            return Ok(0);
+        }
+
+        if total_weighted_stake == 0 {
+            // Nobody is earning, so pause the stream instead of losing the rewards
+            self.period_finish = self
+                .period_finish
+                .checked_add(current_time - last_update_time)
+                .ok_or(ErrorCode::MathOverflow)?;
+            return Ok(0);
+        }
+
+        let elapsed = (applicable_time - last_update_time) as u128;
+
+        let distributed = if applicable_time >= self.period_finish {
+            // Flush rounding dust at the end of the period
+            self.pending_rewards
+        } else {
+            elapsed
+                .checked_mul(self.reward_rate)
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_div(PRECISION)
+                .ok_or(ErrorCode::MathOverflow)?
+                .min(self.pending_rewards)
+        };
+
+        let reward_increment = distributed
+            .checked_mul(PRECISION)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(total_weighted_stake as u128)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        self.reward_per_token_staked = self
+            .reward_per_token_staked
+            .checked_add(reward_increment)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        self.total_distributed = self
+            .total_distributed
+            .checked_add(distributed)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        self.pending_rewards = self
+            .pending_rewards
+            .checked_sub(distributed)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(distributed)
+    }
+
+    /// Rewards distributed but not yet claimed plus rewards still to be streamed
+    pub fn outstanding_rewards(&self) -> Result<u128> {
+        self.total_distributed
+            .checked_sub(self.total_claimed)
+            .and_then(|unclaimed| unclaimed.checked_add(self.pending_rewards))
+            .ok_or(ErrorCode::MathOverflow.into())
+    }
+
+    /// Stop the stream for good, dropping rewards that were not streamed yet.
+    /// The stream must be updated to `current_time` first.
+    pub fn halt(&mut self, current_time: i64) {
+        self.pending_rewards = 0;
+        self.reward_rate = 0;
+        self.period_finish = current_time;
+        self.last_update_time = current_time;
+    }
+
+    /// Add `amount` to the stream and emit everything not yet streamed linearly over `duration`.
+    /// The stream must be updated to `current_time` first.
+    pub fn notify_reward_amount(&mut self, amount: u64, duration: u64, current_time: i64) -> Result<()> {
+        require!(duration > 0, ErrorCode::InvalidRewardDuration);
+
+        // Leftover of an unfinished period is still in pending_rewards and rolls into the new one
+        self.pending_rewards = self
+            .pending_rewards
+            .checked_add(amount as u128)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        self.reward_rate = self
+            .pending_rewards
+            .checked_mul(PRECISION)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(duration as u128)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        self.last_update_time = current_time;
+        self.period_finish = current_time
+            .checked_add(duration as i64)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -1,6 +1,10 @@
 use anchor_lang::prelude::*;
 
-pub const MAX_UNSTAKE_REQUESTS: usize = 32;
+use crate::{
+    constants::BPS_DENOMINATOR,
+    error::ErrorCode,
+    state::{stake_lock::MAX_STAKE_LOCKS, stake_vault::MAX_REWARD_STREAMS, StakeLock, VestingSchedule},
+};
 
 #[account]
 #[derive(Debug, InitSpace)]
@@ -10,36 +14,94 @@ pub struct UserStake {
     pub stake_vault: Pubkey,
     pub staked_amount: u64,
     pub active_stake_amount: u64,
+    /// Active stake including lockup boosts, used for reward accounting
+    pub weighted_stake_amount: u64,
     pub vested_stake_amount: u64,
-    #[max_len(MAX_UNSTAKE_REQUESTS)]
-    pub unstake_requests: Vec<UnstakeRequest>,
-    pub unstake_request_count: u64, //can just use unstake_requests.len() instead
-    pub reward_state: UserRewardState,
+    /// Unclaimed remainder of all open unstake requests
+    pub unstaking_amount: u64,
+    /// Number of open `UnstakeRequest` accounts
+    pub open_unstake_requests: u64,
+    /// Lockups on part of the active stake, dropped once expired
+    #[max_len(MAX_STAKE_LOCKS)]
+    pub stake_locks: Vec<StakeLock>,
+    /// Id given to the next unstake request, starts over at 0 when the user stake is created again
+    pub unstake_request_count: u64,
+    /// Taken from `StakeVault::user_stakes_created` when the account is created, seeds the
+    /// unstake request PDAs so a user stake closed and created again never reuses their addresses
+    pub generation: u64,
+    /// Per-stream reward state, index-aligned with `StakeVault::reward_streams`
+    #[max_len(MAX_REWARD_STREAMS)]
+    pub reward_streams: Vec<UserRewardState>,
     pub last_update_timestamp: i64,
     pub bump: u8,
-    pub padding: [u8; 8],
+    /// Lets anyone compound this user's staked-mint rewards into active stake
+    pub auto_compound: bool,
+    /// Wallet allowed to claim and compound on the owner's behalf, payouts still go to the owner
+    pub delegate: Option<Pubkey>,
+    pub padding: [u8; 7],
 }
 
 impl UserStake {
-    /// Get the total amount currently unstaking (in linear vesting) across all requests.
-    /// This is the remaining unclaimed amount that will gradually become claimable.
-    pub fn get_total_unstaking_amount(&self) -> u64 {
-        self.unstake_requests
+    /// Whether `key` may claim or compound for this user
+    pub fn is_owner_or_delegate(&self, key: &Pubkey) -> bool {
+        self.owner == *key || self.delegate.as_ref() == Some(key)
+    }
+
+    /// Active stake still under lockup
+    pub fn get_locked_amount(&self, current_time: i64) -> u64 {
+        self.stake_locks
             .iter()
-            .map(|req| req.total_amount.saturating_sub(req.claimed_amount))
+            .filter(|lock| !lock.is_expired(current_time))
+            .map(|lock| lock.amount)
             .sum()
     }
 
-    /// Get the total claimable amount across all requests
-    pub fn get_total_claimable(&self, current_time: i64, vesting_period_seconds: u64) -> u64 {
-        self.unstake_requests
+    /// Lock part of the active stake, merging with an existing lock ending at the same time
+    pub fn add_stake_lock(&mut self, lock: StakeLock) -> Result<()> {
+        if let Some(existing) = self.stake_locks.iter_mut().find(|existing| {
+            existing.unlock_time == lock.unlock_time && existing.multiplier_bps == lock.multiplier_bps
+        }) {
+            existing.amount = existing
+                .amount
+                .checked_add(lock.amount)
+                .ok_or(ErrorCode::MathOverflow)?;
+            return Ok(());
+        }
+
+        require!(
+            self.stake_locks.len() < MAX_STAKE_LOCKS,
+            ErrorCode::MaxStakeLocksReached
+        );
+        self.stake_locks.push(lock);
+        Ok(())
+    }
+
+    /// Governance voting power: active stake plus lockup boosts decayed by the
+    /// remaining lock time. Amounts in unstake requests carry no voting power.
+    pub fn voting_power(&self, current_time: i64) -> u64 {
+        self.stake_locks
             .iter()
-            .map(|req| req.claimable_amount(current_time, vesting_period_seconds))
-            .sum()
+            .map(|lock| lock.decayed_bonus_weight(current_time))
+            .fold(self.active_stake_amount, |power, bonus| power.saturating_add(bonus))
     }
 
-    pub fn cleanup_claimed_requests(&mut self) {
-        self.unstake_requests.retain(|req| !req.is_fully_claimed());
+    /// Drop expired lockups and recompute the weighted stake.
+    /// Returns the new weighted stake amount.
+    pub fn recompute_weighted_stake(&mut self, current_time: i64) -> Result<u64> {
+        self.stake_locks.retain(|lock| !lock.is_expired(current_time));
+
+        let bonus_weight = self
+            .stake_locks
+            .iter()
+            .try_fold(0u64, |acc, lock| acc.checked_add(lock.bonus_weight()))
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        self.weighted_stake_amount = self
+            .active_stake_amount
+            .checked_add(bonus_weight)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(self.weighted_stake_amount)
     }
 }
 
@@ -53,37 +115,141 @@ pub struct UserRewardState {
     pub total_claimed: u64,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace, Default)]
+/// A pending withdrawal vesting out of the vault, one account per request
+#[account]
+#[derive(Debug, InitSpace)]
 pub struct UnstakeRequest {
+    pub user_stake: Pubkey,
+    /// Taken from `UserStake::unstake_request_count`, unique within the user stake's generation
+    pub id: u64,
     pub total_amount: u64,
     pub claimed_amount: u64,
+    /// Moved back to active stake by cancel_unstake, the rest vests over the original schedule
+    pub cancelled_amount: u64,
     pub start_time: i64,
+    /// Vesting period of the vault when the request was created
+    pub vesting_period_seconds: u64,
+    /// Cliff of the vault when the request was created
+    pub cliff_period_seconds: u64,
+    /// Unlock curve of the vault when the request was created
+    pub vesting_schedule: VestingSchedule,
+    pub bump: u8,
 }
 
 impl UnstakeRequest {
     pub fn is_fully_claimed(&self) -> bool {
-        self.total_amount > 0 && self.claimed_amount >= self.total_amount
+        self.total_amount > 0 && self.claimed_amount >= self.net_amount()
+    }
+
+    /// Part of the request still to be paid out in total, cancellations excluded
+    pub fn net_amount(&self) -> u64 {
+        self.total_amount.saturating_sub(self.cancelled_amount)
+    }
+
+    /// Part of the request not claimed yet, vested or not
+    pub fn remaining_amount(&self) -> u64 {
+        self.net_amount().saturating_sub(self.claimed_amount)
+    }
+
+    pub fn end_time(&self) -> i64 {
+        self.start_time + self.vesting_period_seconds as i64
+    }
+
+    pub fn cliff_time(&self) -> i64 {
+        self.start_time + self.cliff_period_seconds as i64
     }
 
-    pub fn claimable_amount(&self, current_time: i64, vesting_period_seconds: u64) -> u64 {
-        if current_time <= self.start_time {
+    /// Part of the net amount unlocked by `current_time`, claimed or not.
+    /// What is left after cancelling vests over the original start and end time,
+    /// so cancelling never brings an unlock forward.
+    pub fn vested_amount(&self, current_time: i64) -> u64 {
+        if current_time <= self.start_time || current_time < self.cliff_time() {
             return 0;
         };
 
         let elapsed_time = (current_time - self.start_time) as u64;
-        let end_time = self.start_time + vesting_period_seconds as i64;
-
-        let vested_amount = if current_time >= end_time {
-            self.total_amount
-        } else {
-            self.total_amount
-                .checked_mul(elapsed_time)
-                .unwrap_or(0)
-                .checked_div(vesting_period_seconds)
-                .unwrap_or(0)
+
+        self.vesting_schedule.vested_amount(
+            self.net_amount(),
+            elapsed_time,
+            self.vesting_period_seconds,
+        )
+    }
+
+    /// Zero while claims made before a cancel are ahead of what now vests
+    pub fn claimable_amount(&self, current_time: i64) -> u64 {
+        self.vested_amount(current_time).saturating_sub(self.claimed_amount)
+    }
+
+    /// Penalty for releasing the unvested part of the request right away.
+    /// Scales with the share of the vesting period still remaining.
+    pub fn early_exit_penalty(&self, current_time: i64, penalty_bps: u16) -> u64 {
+        let unvested_amount = self
+            .remaining_amount()
+            .saturating_sub(self.claimable_amount(current_time));
+
+        let remaining_time = self.end_time().saturating_sub(current_time.max(self.start_time));
+        if unvested_amount == 0 || remaining_time <= 0 || self.vesting_period_seconds == 0 {
+            return 0;
+        }
+
+        let penalty = unvested_amount as u128 * penalty_bps as u128 * remaining_time as u128
+            / (BPS_DENOMINATOR as u128 * self.vesting_period_seconds as u128);
+
+        penalty as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unstake_request(total_amount: u64, claimed_amount: u64, cancelled_amount: u64) -> UnstakeRequest {
+        UnstakeRequest {
+            user_stake: Pubkey::default(),
+            id: 0,
+            total_amount,
+            claimed_amount,
+            cancelled_amount,
+            start_time: 0,
+            vesting_period_seconds: 100,
+            cliff_period_seconds: 0,
+            vesting_schedule: VestingSchedule::Linear,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn remainder_vests_over_the_original_schedule_after_cancelling() {
+        let request = unstake_request(1_000, 200, 400);
+
+        // The 600 left after cancelling vests from 0 to 100, less the 200 already claimed
+        assert_eq!(request.remaining_amount(), 400);
+        assert_eq!(request.claimable_amount(30), 0);
+        assert_eq!(request.claimable_amount(60), 160);
+        assert_eq!(request.claimable_amount(90), 340);
+        assert_eq!(request.claimable_amount(100), 400);
+        assert!(!request.is_fully_claimed());
+
+        let request = UnstakeRequest {
+            claimed_amount: 600,
+            ..request
         };
+        assert_eq!(request.remaining_amount(), 0);
+        assert!(request.is_fully_claimed());
+    }
+
+    #[test]
+    fn cancelling_at_the_start_does_not_speed_up_vesting() {
+        // Unstake 1_000_000 and cancel all but 1_000 straight away
+        let request = unstake_request(1_000_000, 0, 999_000);
+
+        assert_eq!(request.claimable_amount(1), 10);
+        assert_eq!(request.claimable_amount(50), 500);
+        assert_eq!(request.claimable_amount(99), 990);
+        assert_eq!(request.claimable_amount(100), 1_000);
 
-        // Claimable = vested - already claimed
-        vested_amount.saturating_sub(self.claimed_amount)
+        // The unvested part and its penalty shrink with the amount kept
+        assert_eq!(request.early_exit_penalty(50, 1_000), 25);
     }
 }
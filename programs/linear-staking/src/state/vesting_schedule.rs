@@ -0,0 +1,164 @@
+use anchor_lang::prelude::*;
+
+use crate::error::ErrorCode;
+
+/// Unlock curve applied to unstake requests over their vesting period.
+/// Whatever the curve, everything is unlocked once the vesting period ends.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace, Default)]
+pub enum VestingSchedule {
+    /// Unlocks continuously at a constant rate
+    #[default]
+    Linear,
+    /// Unlocks in `tranches` equal steps spread evenly over the vesting period
+    Step { tranches: u8 },
+    /// Unlocks half of the still-locked amount every `half_life_seconds`
+    ExponentialDecay { half_life_seconds: u64 },
+}
+
+impl VestingSchedule {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            VestingSchedule::Linear => {}
+            VestingSchedule::Step { tranches } => {
+                require!(*tranches > 0, ErrorCode::InvalidVestingSchedule);
+            }
+            VestingSchedule::ExponentialDecay { half_life_seconds } => {
+                require!(*half_life_seconds > 0, ErrorCode::InvalidVestingSchedule);
+            }
+        }
+        Ok(())
+    }
+
+    /// Amount of `total_amount` unlocked after `elapsed_time` of a `vesting_period_seconds` schedule
+    pub fn vested_amount(&self, total_amount: u64, elapsed_time: u64, vesting_period_seconds: u64) -> u64 {
+        if elapsed_time >= vesting_period_seconds {
+            return total_amount;
+        }
+
+        match *self {
+            VestingSchedule::Linear => total_amount
+                .checked_mul(elapsed_time)
+                .unwrap_or(0)
+                .checked_div(vesting_period_seconds)
+                .unwrap_or(0),
+            VestingSchedule::Step { tranches } => {
+                let tranches = tranches as u128;
+                let completed_tranches = (elapsed_time as u128 * tranches) / vesting_period_seconds as u128;
+
+                (total_amount as u128 * completed_tranches / tranches) as u64
+            }
+            VestingSchedule::ExponentialDecay { half_life_seconds } => {
+                let halvings = elapsed_time / half_life_seconds;
+                if halvings >= u64::BITS as u64 {
+                    return total_amount;
+                }
+
+                // Locked amount at the last full half-life, interpolated linearly within the current one
+                let locked_at_halving = total_amount >> halvings;
+                let unlocking = (locked_at_halving - (locked_at_halving >> 1)) as u128;
+                let into_half_life = (elapsed_time % half_life_seconds) as u128;
+                let locked = locked_at_halving
+                    - (unlocking * into_half_life / half_life_seconds as u128) as u64;
+
+                total_amount - locked
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOTAL: u64 = 1_024;
+    const PERIOD: u64 = 100;
+
+    #[test]
+    fn linear_unlocks_proportionally() {
+        let schedule = VestingSchedule::Linear;
+
+        assert_eq!(schedule.vested_amount(TOTAL, 0, PERIOD), 0);
+        assert_eq!(schedule.vested_amount(TOTAL, 25, PERIOD), 256);
+        assert_eq!(schedule.vested_amount(TOTAL, 99, PERIOD), 1_013);
+        assert_eq!(schedule.vested_amount(TOTAL, PERIOD, PERIOD), TOTAL);
+    }
+
+    #[test]
+    fn step_unlocks_at_tranche_boundaries() {
+        let schedule = VestingSchedule::Step { tranches: 4 };
+
+        assert_eq!(schedule.vested_amount(TOTAL, 24, PERIOD), 0);
+        assert_eq!(schedule.vested_amount(TOTAL, 25, PERIOD), 256);
+        assert_eq!(schedule.vested_amount(TOTAL, 49, PERIOD), 256);
+        assert_eq!(schedule.vested_amount(TOTAL, 50, PERIOD), 512);
+        assert_eq!(schedule.vested_amount(TOTAL, 75, PERIOD), 768);
+        assert_eq!(schedule.vested_amount(TOTAL, 99, PERIOD), 768);
+    }
+
+    #[test]
+    fn step_rounds_uneven_tranches_down() {
+        let schedule = VestingSchedule::Step { tranches: 3 };
+
+        // Tranches end at 3.33s, 6.67s and 10s
+        assert_eq!(schedule.vested_amount(100, 3, 10), 0);
+        assert_eq!(schedule.vested_amount(100, 4, 10), 33);
+        assert_eq!(schedule.vested_amount(100, 6, 10), 33);
+        assert_eq!(schedule.vested_amount(100, 7, 10), 66);
+        assert_eq!(schedule.vested_amount(100, 9, 10), 66);
+    }
+
+    #[test]
+    fn exponential_decay_halves_each_half_life() {
+        let schedule = VestingSchedule::ExponentialDecay { half_life_seconds: 10 };
+
+        assert_eq!(schedule.vested_amount(TOTAL, 0, PERIOD), 0);
+        assert_eq!(schedule.vested_amount(TOTAL, 10, PERIOD), 512);
+        assert_eq!(schedule.vested_amount(TOTAL, 20, PERIOD), 768);
+        assert_eq!(schedule.vested_amount(TOTAL, 30, PERIOD), 896);
+    }
+
+    #[test]
+    fn exponential_decay_interpolates_within_a_half_life() {
+        let schedule = VestingSchedule::ExponentialDecay { half_life_seconds: 10 };
+
+        // Halfway through the first half-life, half of the 512 unlocking in it
+        assert_eq!(schedule.vested_amount(TOTAL, 5, PERIOD), 256);
+        // Halfway through the second, 512 + half of 256
+        assert_eq!(schedule.vested_amount(TOTAL, 15, PERIOD), 640);
+        // Late half-lives unlock too little to show before the end of the period
+        assert_eq!(schedule.vested_amount(TOTAL, 99, PERIOD), 1_022);
+    }
+
+    #[test]
+    fn exponential_decay_survives_many_halvings() {
+        let schedule = VestingSchedule::ExponentialDecay { half_life_seconds: 1 };
+
+        assert_eq!(schedule.vested_amount(u64::MAX, 63, 1_000), u64::MAX - 1);
+        assert_eq!(schedule.vested_amount(u64::MAX, 64, 1_000), u64::MAX);
+        assert_eq!(schedule.vested_amount(u64::MAX, 500, 1_000), u64::MAX);
+    }
+
+    #[test]
+    fn every_schedule_flushes_at_the_end_of_the_period() {
+        let schedules = [
+            VestingSchedule::Linear,
+            VestingSchedule::Step { tranches: 3 },
+            VestingSchedule::Step { tranches: u8::MAX },
+            VestingSchedule::ExponentialDecay { half_life_seconds: 10 },
+            VestingSchedule::ExponentialDecay { half_life_seconds: 1_000 },
+        ];
+
+        for schedule in schedules {
+            assert_eq!(schedule.vested_amount(TOTAL, PERIOD, PERIOD), TOTAL, "{schedule:?}");
+            assert_eq!(schedule.vested_amount(TOTAL, PERIOD + 1, PERIOD), TOTAL, "{schedule:?}");
+
+            // Never decreases and never exceeds the total before the end
+            let mut previous = 0;
+            for elapsed in 0..PERIOD {
+                let vested = schedule.vested_amount(TOTAL, elapsed, PERIOD);
+                assert!(vested >= previous && vested <= TOTAL, "{schedule:?} at {elapsed}s");
+                previous = vested;
+            }
+        }
+    }
+}
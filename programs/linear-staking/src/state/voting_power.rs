@@ -0,0 +1,184 @@
+use anchor_lang::prelude::*;
+
+pub const MAX_VOTING_POWER_CHECKPOINTS: usize = 32;
+
+/// History of a user's voting power, readable by governance programs.
+/// Keeps up to `MAX_VOTING_POWER_CHECKPOINTS` checkpoints, oldest first.
+#[account]
+#[derive(Debug, InitSpace)]
+pub struct VotingPowerRecord {
+    pub owner: Pubkey,
+    pub stake_vault: Pubkey,
+    pub user_stake: Pubkey,
+    pub bump: u8,
+    #[max_len(MAX_VOTING_POWER_CHECKPOINTS)]
+    pub checkpoints: Vec<VotingPowerCheckpoint>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace, Default)]
+pub struct VotingPowerCheckpoint {
+    pub slot: u64,
+    pub timestamp: i64,
+    pub voting_power: u64,
+}
+
+impl VotingPowerRecord {
+    /// Record the voting power at `slot`, replacing a checkpoint already taken in the same
+    /// slot. Nothing is recorded while the voting power is unchanged. Once the history is
+    /// full the oldest checkpoint is dropped if `voting_power_at` still answers for every
+    /// slot from `retention_slot` on without it, None keeps just the latest checkpoints.
+    /// Otherwise the two newest checkpoints are merged into the older one at the lower of
+    /// their voting powers, so a full history never blocks a stake change and never
+    /// overstates voting power, only understates it between those two slots.
+    pub fn push_checkpoint(&mut self, checkpoint: VotingPowerCheckpoint, retention_slot: Option<u64>) {
+        if let Some(last) = self.checkpoints.last_mut() {
+            if last.voting_power == checkpoint.voting_power {
+                return;
+            }
+            if last.slot == checkpoint.slot {
+                *last = checkpoint;
+                return;
+            }
+        }
+
+        if self.checkpoints.len() >= MAX_VOTING_POWER_CHECKPOINTS {
+            let is_evictable = match retention_slot {
+                Some(retention_slot) => self.checkpoints[1].slot <= retention_slot,
+                None => true,
+            };
+
+            if is_evictable {
+                self.checkpoints.remove(0);
+            } else if let Some(newest) = self.checkpoints.pop() {
+                if let Some(last) = self.checkpoints.last_mut() {
+                    last.voting_power = last.voting_power.min(newest.voting_power);
+                }
+            }
+        }
+        self.checkpoints.push(checkpoint);
+    }
+
+    /// Voting power of the latest checkpoint taken at or before `slot`
+    pub fn voting_power_at(&self, slot: u64) -> Option<u64> {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.slot <= slot)
+            .map(|checkpoint| checkpoint.voting_power)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_record() -> VotingPowerRecord {
+        VotingPowerRecord {
+            owner: Pubkey::default(),
+            stake_vault: Pubkey::default(),
+            user_stake: Pubkey::default(),
+            bump: 0,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    fn checkpoint(slot: u64, voting_power: u64) -> VotingPowerCheckpoint {
+        VotingPowerCheckpoint {
+            slot,
+            timestamp: slot as i64,
+            voting_power,
+        }
+    }
+
+    /// Checkpoints at slots 10, 20, ..., 320 with voting power 1, 2, ..., 32
+    fn full_record() -> VotingPowerRecord {
+        let mut record = empty_record();
+        for i in 1..=MAX_VOTING_POWER_CHECKPOINTS as u64 {
+            record.push_checkpoint(checkpoint(i * 10, i), None);
+        }
+        record
+    }
+
+    #[test]
+    fn voting_power_at_reads_the_latest_checkpoint_before_the_slot() {
+        let mut record = empty_record();
+        record.push_checkpoint(checkpoint(10, 100), None);
+        record.push_checkpoint(checkpoint(20, 50), None);
+
+        assert_eq!(record.voting_power_at(9), None);
+        assert_eq!(record.voting_power_at(10), Some(100));
+        assert_eq!(record.voting_power_at(19), Some(100));
+        assert_eq!(record.voting_power_at(20), Some(50));
+        assert_eq!(record.voting_power_at(1_000), Some(50));
+    }
+
+    #[test]
+    fn unchanged_voting_power_is_not_recorded() {
+        let mut record = empty_record();
+        record.push_checkpoint(checkpoint(10, 100), None);
+        record.push_checkpoint(checkpoint(20, 100), None);
+        assert_eq!(record.checkpoints.len(), 1);
+
+        // A change in the same slot replaces the checkpoint
+        record.push_checkpoint(checkpoint(10, 80), None);
+        assert_eq!(record.checkpoints.len(), 1);
+        assert_eq!(record.voting_power_at(10), Some(80));
+    }
+
+    #[test]
+    fn eviction_keeps_history_from_the_retention_slot_on() {
+        // Slot 20 is the latest checkpoint at or before 25, the one at 10 can go
+        let mut record = full_record();
+        record.push_checkpoint(checkpoint(330, 33), Some(25));
+        assert_eq!(record.checkpoints.len(), MAX_VOTING_POWER_CHECKPOINTS);
+        assert_eq!(record.voting_power_at(15), None);
+        assert_eq!(record.voting_power_at(25), Some(2));
+        assert_eq!(record.voting_power_at(330), Some(33));
+
+        // Dropping the checkpoint at 20 would lose the answer for slot 25, the newest
+        // checkpoints at 320 and 330 are merged at the lower voting power instead
+        record.push_checkpoint(checkpoint(340, 34), Some(25));
+        assert_eq!(record.checkpoints.len(), MAX_VOTING_POWER_CHECKPOINTS);
+        assert_eq!(record.voting_power_at(25), Some(2));
+        assert_eq!(record.voting_power_at(310), Some(31));
+        assert_eq!(record.voting_power_at(335), Some(32));
+        assert_eq!(record.voting_power_at(340), Some(34));
+    }
+
+    #[test]
+    fn history_filled_by_a_third_party_never_blocks_a_stake_change() {
+        // Dust deposits from someone else push the voting power up one by one
+        let mut record = empty_record();
+        record.push_checkpoint(checkpoint(10, 1_000), Some(10));
+        for i in 1..=100 {
+            record.push_checkpoint(checkpoint(10 + i, 1_000 + i), Some(10));
+        }
+        assert_eq!(record.checkpoints.len(), MAX_VOTING_POWER_CHECKPOINTS);
+
+        // The owner unstakes, the new voting power is recorded exactly
+        record.push_checkpoint(checkpoint(200, 400), Some(10));
+        assert_eq!(record.checkpoints.len(), MAX_VOTING_POWER_CHECKPOINTS);
+        assert_eq!(record.voting_power_at(10), Some(1_000));
+        assert_eq!(record.voting_power_at(200), Some(400));
+
+        // Merged slots never read above what the user actually had
+        for slot in 10..=110 {
+            assert!(record.voting_power_at(slot).unwrap() <= 1_000 + (slot - 10));
+        }
+
+        // Exiting brings it to zero just the same
+        record.push_checkpoint(checkpoint(201, 0), Some(10));
+        assert_eq!(record.voting_power_at(201), Some(0));
+    }
+
+    #[test]
+    fn eviction_without_retention_drops_the_oldest() {
+        let mut record = full_record();
+        record.push_checkpoint(checkpoint(330, 33), None);
+
+        assert_eq!(record.checkpoints.len(), MAX_VOTING_POWER_CHECKPOINTS);
+        assert_eq!(record.voting_power_at(10), None);
+        assert_eq!(record.voting_power_at(20), Some(2));
+        assert_eq!(record.voting_power_at(335), Some(33));
+    }
+}
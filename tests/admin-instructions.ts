@@ -7,8 +7,9 @@ import {
   TOKEN_PROGRAM_ID,
   mintTo,
   getAccount,
+  transfer,
 } from "@solana/spl-token";
-import { PublicKey, SystemProgram } from "@solana/web3.js";
+import { Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram, Transaction } from "@solana/web3.js";
 import { assert } from "chai";
 
 describe("admin-instructions", () => {
@@ -23,24 +24,40 @@ describe("admin-instructions", () => {
   let adminTokenAccount: any;
   let stakeVault: PublicKey;
   let vaultTokenAccount: PublicKey;
+  let rewardTokenAccount: PublicKey;
   let transferAuthority: PublicKey;
   let userStake: PublicKey;
   let eventAuthority: PublicKey;
 
+  // Staker whose voting power history was filled by someone else
+  const victim = Keypair.generate();
+  let victimStake: PublicKey;
+  let victimRequest: PublicKey;
+
   // Constants (matching Rust seeds)
   const STAKE_VAULT_SEED = Buffer.from("stake_vault");
   const STAKE_VAULT_TOKEN_ACCOUNT_SEED = Buffer.from("stake_vault_token_account");
+  const REWARD_TOKEN_ACCOUNT_SEED = Buffer.from("reward_token_account");
   const USER_STAKE_SEED = Buffer.from("user_stake");
   const TRANSFER_AUTHORITY_SEED = Buffer.from("transfer_authority");
   const EVENT_AUTHORITY_SEED = Buffer.from("__event_authority");
+  const VOTING_POWER_SEED = Buffer.from("voting_power");
+  const UNSTAKE_REQUEST_SEED = Buffer.from("unstake_request");
 
   // Test amounts
   const INITIAL_MINT_AMOUNT = 1_000_000_000_000; // 1000 tokens (9 decimals)
   const STAKE_AMOUNT = 100_000_000_000; // 100 tokens
 
+  // Checkpoints kept per voting power record
+  const MAX_VOTING_POWER_CHECKPOINTS = 32;
+
   // Initial vesting period for testing (30 seconds)
   const INITIAL_VESTING_PERIOD = 30;
 
+  // Voting power record checkpointed on every stake change
+  const votingPowerPda = (stake: PublicKey): PublicKey =>
+    PublicKey.findProgramAddressSync([VOTING_POWER_SEED, stake.toBuffer()], program.programId)[0];
+
   before(async () => {
     // Create token mint
     tokenMint = await createMint(
@@ -70,22 +87,27 @@ describe("admin-instructions", () => {
 
     // Derive PDAs
     [stakeVault] = PublicKey.findProgramAddressSync(
-      [STAKE_VAULT_SEED],
+      [STAKE_VAULT_SEED, tokenMint.toBuffer()],
       program.programId
     );
 
     [vaultTokenAccount] = PublicKey.findProgramAddressSync(
-      [STAKE_VAULT_TOKEN_ACCOUNT_SEED],
+      [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer()],
+      program.programId
+    );
+
+    [rewardTokenAccount] = PublicKey.findProgramAddressSync(
+      [REWARD_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer(), tokenMint.toBuffer()],
       program.programId
     );
 
     [transferAuthority] = PublicKey.findProgramAddressSync(
-      [TRANSFER_AUTHORITY_SEED],
+      [TRANSFER_AUTHORITY_SEED, stakeVault.toBuffer()],
       program.programId
     );
 
     [userStake] = PublicKey.findProgramAddressSync(
-      [USER_STAKE_SEED, admin.publicKey.toBuffer()],
+      [USER_STAKE_SEED, stakeVault.toBuffer(), admin.publicKey.toBuffer()],
       program.programId
     );
 
@@ -99,12 +121,16 @@ describe("admin-instructions", () => {
     const tx = await program.methods
       .initialize({
         vestingPeriod: new BN(INITIAL_VESTING_PERIOD),
+        cliffPeriod: null,
+        vestingSchedule: null,
       })
       .accountsStrict({
         admin: admin.publicKey,
         tokenMint: tokenMint,
+        rewardMint: tokenMint,
         stakeVault: stakeVault,
         vaultTokenAccount: vaultTokenAccount,
+        rewardTokenAccount: rewardTokenAccount,
         transferAuthority: transferAuthority,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
@@ -125,13 +151,19 @@ describe("admin-instructions", () => {
     await program.methods
       .depositStake({
         amount: new BN(STAKE_AMOUNT),
+        lockup: null,
       })
       .accountsStrict({
         owner: admin.publicKey,
         userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
         stakeVault: stakeVault,
         vaultTokenAccount: vaultTokenAccount,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        transferAuthority: null,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         eventAuthority: eventAuthority,
@@ -254,21 +286,13 @@ describe("admin-instructions", () => {
   // Emergency Withdraw Tests
   // =========================================================================
 
-  it("9. should fail emergency withdraw when not paused", async () => {
+  it("9. should fail to enable emergency mode when not paused", async () => {
     try {
       await program.methods
-        .emergencyWithdraw({
-          amount: new BN(STAKE_AMOUNT),
-        })
+        .enableEmergencyMode()
         .accountsStrict({
           admin: admin.publicKey,
           stakeVault: stakeVault,
-          vaultTokenAccount: vaultTokenAccount,
-          adminTokenAccount: adminTokenAccount.address,
-          transferAuthority: transferAuthority,
-          tokenProgram: TOKEN_PROGRAM_ID,
-          eventAuthority: eventAuthority,
-          program: program.programId,
         })
         .rpc();
 
@@ -278,8 +302,8 @@ describe("admin-instructions", () => {
     }
   });
 
-  it("10. should emergency withdraw when paused", async () => {
-    // First pause the vault
+  it("10. should fail emergency withdraw outside emergency mode", async () => {
+    // Pausing alone does not allow withdrawing funds
     await program.methods
       .pauseVault()
       .accountsStrict({
@@ -288,101 +312,17 @@ describe("admin-instructions", () => {
       })
       .rpc();
 
-    const vaultBalanceBefore = await getAccount(
-      provider.connection,
-      vaultTokenAccount
-    );
-    const adminBalanceBefore = await getAccount(
-      provider.connection,
-      adminTokenAccount.address
-    );
-
-    const withdrawAmount = STAKE_AMOUNT / 2; // Withdraw half
-
-    const tx = await program.methods
-      .emergencyWithdraw({
-        amount: new BN(withdrawAmount),
-      })
-      .accountsStrict({
-        admin: admin.publicKey,
-        stakeVault: stakeVault,
-        vaultTokenAccount: vaultTokenAccount,
-        adminTokenAccount: adminTokenAccount.address,
-        transferAuthority: transferAuthority,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        eventAuthority: eventAuthority,
-        program: program.programId,
-      })
-      .rpc();
-
-    console.log("Emergency withdraw tx:", tx);
-
-    const vaultBalanceAfter = await getAccount(
-      provider.connection,
-      vaultTokenAccount
-    );
-    const adminBalanceAfter = await getAccount(
-      provider.connection,
-      adminTokenAccount.address
-    );
-
-    assert.equal(
-      Number(vaultBalanceBefore.amount) - Number(vaultBalanceAfter.amount),
-      withdrawAmount
-    );
-    assert.equal(
-      Number(adminBalanceAfter.amount) - Number(adminBalanceBefore.amount),
-      withdrawAmount
-    );
-  });
-
-  it("11. should emergency withdraw all with amount=0", async () => {
-    const vaultBalanceBefore = await getAccount(
-      provider.connection,
-      vaultTokenAccount
-    );
-
-    const tx = await program.methods
-      .emergencyWithdraw({
-        amount: new BN(0), // 0 means withdraw all
-      })
-      .accountsStrict({
-        admin: admin.publicKey,
-        stakeVault: stakeVault,
-        vaultTokenAccount: vaultTokenAccount,
-        adminTokenAccount: adminTokenAccount.address,
-        transferAuthority: transferAuthority,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        eventAuthority: eventAuthority,
-        program: program.programId,
-      })
-      .rpc();
-
-    console.log("Emergency withdraw all tx:", tx);
-
-    const vaultBalanceAfter = await getAccount(
-      provider.connection,
-      vaultTokenAccount
-    );
-
-    assert.equal(Number(vaultBalanceAfter.amount), 0);
-    console.log(
-      "Withdrew all remaining tokens:",
-      Number(vaultBalanceBefore.amount)
-    );
-  });
-
-  it("12. should fail emergency withdraw with insufficient balance", async () => {
     try {
       await program.methods
         .emergencyWithdraw({
-          amount: new BN(1_000_000_000_000), // More than vault has
+          amount: new BN(STAKE_AMOUNT / 2),
         })
         .accountsStrict({
           admin: admin.publicKey,
           stakeVault: stakeVault,
-          vaultTokenAccount: vaultTokenAccount,
+          sourceTokenAccount: vaultTokenAccount,
           adminTokenAccount: adminTokenAccount.address,
+          mint: tokenMint,
           transferAuthority: transferAuthority,
           tokenProgram: TOKEN_PROGRAM_ID,
           eventAuthority: eventAuthority,
@@ -392,7 +332,52 @@ describe("admin-instructions", () => {
 
       assert.fail("Should have thrown an error");
     } catch (error: any) {
-      assert.include(error.message, "InsufficientVaultBalance");
+      assert.include(error.message, "EmergencyModeNotActive");
+    }
+  });
+
+  it("11. should fail emergency exit outside emergency mode", async () => {
+    try {
+      await program.methods
+        .emergencyExit()
+        .accountsStrict({
+          owner: admin.publicKey,
+          userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          stakeVault: stakeVault,
+          userTokenAccount: adminTokenAccount.address,
+          tokenMint: tokenMint,
+          vaultTokenAccount: vaultTokenAccount,
+          transferAuthority: transferAuthority,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "EmergencyModeNotActive");
+    }
+  });
+
+  it("12. should fail to enable emergency mode without the guardian role", async () => {
+    const outsider = Keypair.generate();
+
+    try {
+      await program.methods
+        .enableEmergencyMode()
+        .accountsStrict({
+          admin: outsider.publicKey,
+          stakeVault: stakeVault,
+        })
+        .signers([outsider])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "Unauthorized");
     }
   });
 
@@ -443,13 +428,19 @@ describe("admin-instructions", () => {
       await program.methods
         .depositStake({
           amount: new BN(STAKE_AMOUNT),
+          lockup: null,
         })
         .accountsStrict({
           owner: admin.publicKey,
           userTokenAccount: adminTokenAccount.address,
+          tokenMint: tokenMint,
           stakeVault: stakeVault,
           vaultTokenAccount: vaultTokenAccount,
           userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          receiptMint: null,
+          userReceiptTokenAccount: null,
+          transferAuthority: null,
           systemProgram: SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
           eventAuthority: eventAuthority,
@@ -508,13 +499,19 @@ describe("admin-instructions", () => {
     const tx = await program.methods
       .depositStake({
         amount: new BN(STAKE_AMOUNT),
+        lockup: null,
       })
       .accountsStrict({
         owner: admin.publicKey,
         userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
         stakeVault: stakeVault,
         vaultTokenAccount: vaultTokenAccount,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        transferAuthority: null,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         eventAuthority: eventAuthority,
@@ -557,4 +554,510 @@ describe("admin-instructions", () => {
       vaultStateBefore.permissions.allowWithdrawals
     );
   });
+
+  // =========================================================================
+  // Cliff Period Tests
+  // =========================================================================
+
+  it("19. should update cliff period", async () => {
+    const NEW_CLIFF_PERIOD = 10;
+
+    const tx = await program.methods
+      .updateCliffPeriod({
+        newCliffPeriodSeconds: new BN(NEW_CLIFF_PERIOD),
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    console.log("Update cliff period tx:", tx);
+
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.cliffPeriodSeconds.toNumber(), NEW_CLIFF_PERIOD);
+  });
+
+  it("20. should fail to set a cliff longer than the vesting period", async () => {
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+
+    try {
+      await program.methods
+        .updateCliffPeriod({
+          newCliffPeriodSeconds: vaultState.vestingPeriodSeconds.addn(1),
+        })
+        .accountsStrict({
+          admin: admin.publicKey,
+          stakeVault: stakeVault,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "InvalidCliffPeriod");
+    }
+  });
+
+  // =========================================================================
+  // Vesting Schedule Tests
+  // =========================================================================
+
+  it("21. should switch new unstake requests to a step schedule", async () => {
+    const tx = await program.methods
+      .updateVestingSchedule({
+        newVestingSchedule: { step: { tranches: 4 } },
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    console.log("Update vesting schedule tx:", tx);
+
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.deepEqual(vaultState.vestingSchedule, { step: { tranches: 4 } });
+  });
+
+  it("22. should fail to set a step schedule without tranches", async () => {
+    try {
+      await program.methods
+        .updateVestingSchedule({
+          newVestingSchedule: { step: { tranches: 0 } },
+        })
+        .accountsStrict({
+          admin: admin.publicKey,
+          stakeVault: stakeVault,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "InvalidVestingSchedule");
+    }
+  });
+
+  // =========================================================================
+  // Admin & Role Tests
+  // =========================================================================
+
+  it("23. should only let the assigned pauser pause the vault", async () => {
+    const pauser = Keypair.generate();
+
+    await program.methods
+      .updateRoles({
+        pauser: pauser.publicKey,
+        rewardDepositor: null,
+        configManager: null,
+        emergencyGuardian: null,
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    let vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.roles.pauser.toString(), pauser.publicKey.toString());
+    assert.equal(vaultState.roles.configManager.toString(), admin.publicKey.toString());
+
+    try {
+      await program.methods
+        .pauseVault()
+        .accountsStrict({
+          admin: admin.publicKey,
+          stakeVault: stakeVault,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "Unauthorized");
+    }
+
+    await program.methods
+      .pauseVault()
+      .accountsStrict({
+        admin: pauser.publicKey,
+        stakeVault: stakeVault,
+      })
+      .signers([pauser])
+      .rpc();
+
+    await program.methods
+      .unpauseVault()
+      .accountsStrict({
+        admin: pauser.publicKey,
+        stakeVault: stakeVault,
+      })
+      .signers([pauser])
+      .rpc();
+
+    await program.methods
+      .updateRoles({
+        pauser: admin.publicKey,
+        rewardDepositor: null,
+        configManager: null,
+        emergencyGuardian: null,
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.roles.pauser.toString(), admin.publicKey.toString());
+    assert.isFalse(vaultState.isPaused);
+  });
+
+  it("24. should transfer admin in two steps", async () => {
+    const newAdmin = Keypair.generate();
+
+    await program.methods
+      .proposeAdmin({ newAdmin: newAdmin.publicKey })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    // Proposing alone does not hand over control
+    let vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.admin.toString(), admin.publicKey.toString());
+    assert.equal(vaultState.pendingAdmin.toString(), newAdmin.publicKey.toString());
+
+    await program.methods
+      .acceptAdmin()
+      .accountsStrict({
+        newAdmin: newAdmin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .signers([newAdmin])
+      .rpc();
+
+    vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.admin.toString(), newAdmin.publicKey.toString());
+    assert.equal(vaultState.pendingAdmin.toString(), PublicKey.default.toString());
+
+    // Hand adminship back so later tests keep working
+    await program.methods
+      .proposeAdmin({ newAdmin: admin.publicKey })
+      .accountsStrict({
+        admin: newAdmin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .signers([newAdmin])
+      .rpc();
+
+    await program.methods
+      .acceptAdmin()
+      .accountsStrict({
+        newAdmin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.admin.toString(), admin.publicKey.toString());
+  });
+
+  it("25. should keep stake changes working once a third party fills the voting power history", async () => {
+    [victimStake] = PublicKey.findProgramAddressSync(
+      [USER_STAKE_SEED, stakeVault.toBuffer(), victim.publicKey.toBuffer()],
+      program.programId
+    );
+
+    // The victim pays rent for its unstake request
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: victim.publicKey,
+          lamports: LAMPORTS_PER_SOL / 10,
+        })
+      )
+    );
+
+    // Governance still reads every slot from now on
+    await program.methods
+      .updateVotingPowerRetention({ retentionSlot: new BN(await provider.connection.getSlot()) })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    // Dust deposits from another wallet, each one a new checkpoint
+    for (let i = 0; i < MAX_VOTING_POWER_CHECKPOINTS + 8; i++) {
+      await program.methods
+        .depositStakeFor({ amount: new BN(1) })
+        .accountsStrict({
+          payer: admin.publicKey,
+          beneficiary: victim.publicKey,
+          payerTokenAccount: adminTokenAccount.address,
+          tokenMint: tokenMint,
+          stakeVault: stakeVault,
+          vaultTokenAccount: vaultTokenAccount,
+          userStake: victimStake,
+          votingPowerRecord: votingPowerPda(victimStake),
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+    }
+
+    let record = await program.account.votingPowerRecord.fetch(votingPowerPda(victimStake));
+    assert.equal(record.checkpoints.length, MAX_VOTING_POWER_CHECKPOINTS);
+
+    const victimStakeState = await program.account.userStake.fetch(victimStake);
+    [victimRequest] = PublicKey.findProgramAddressSync(
+      [
+        UNSTAKE_REQUEST_SEED,
+        victimStake.toBuffer(),
+        victimStakeState.generation.toArrayLike(Buffer, "le", 8),
+        victimStakeState.unstakeRequestCount.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+
+    await program.methods
+      .unstakeRequest({ amount: new BN(10) })
+      .accountsStrict({
+        owner: victim.publicKey,
+        stakeVault: stakeVault,
+        userStake: victimStake,
+        votingPowerRecord: votingPowerPda(victimStake),
+        unstakeRequest: victimRequest,
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .signers([victim])
+      .rpc();
+
+    // The latest checkpoint is exact, the history stays at its cap
+    record = await program.account.votingPowerRecord.fetch(votingPowerPda(victimStake));
+    assert.equal(record.checkpoints.length, MAX_VOTING_POWER_CHECKPOINTS);
+    assert.equal(
+      record.checkpoints[record.checkpoints.length - 1].votingPower.toNumber(),
+      MAX_VOTING_POWER_CHECKPOINTS + 8 - 10
+    );
+  });
+
+  // =========================================================================
+  // Emergency Mode Tests (irreversible, keep last)
+  // =========================================================================
+
+  it("26. should enable emergency mode on a paused vault", async () => {
+    await program.methods
+      .pauseVault()
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    await program.methods
+      .enableEmergencyMode()
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.isTrue(vaultState.isEmergency);
+
+    try {
+      await program.methods
+        .unpauseVault()
+        .accountsStrict({
+          admin: admin.publicKey,
+          stakeVault: stakeVault,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "EmergencyModeActive");
+    }
+  });
+
+  it("27. should let a user with a full voting power history exit in emergency mode", async () => {
+    const victimTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      tokenMint,
+      victim.publicKey
+    );
+    const victimStakeBefore = await program.account.userStake.fetch(victimStake);
+
+    await program.methods
+      .emergencyExit()
+      .accountsStrict({
+        owner: victim.publicKey,
+        userStake: victimStake,
+        votingPowerRecord: votingPowerPda(victimStake),
+        stakeVault: stakeVault,
+        userTokenAccount: victimTokenAccount.address,
+        tokenMint: tokenMint,
+        vaultTokenAccount: vaultTokenAccount,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts([{ pubkey: victimRequest, isWritable: true, isSigner: false }])
+      .signers([victim])
+      .rpc();
+
+    const victimBalance = await getAccount(provider.connection, victimTokenAccount.address);
+    assert.equal(Number(victimBalance.amount), victimStakeBefore.stakedAmount.toNumber());
+
+    const record = await program.account.votingPowerRecord.fetch(votingPowerPda(victimStake));
+    assert.equal(record.checkpoints[record.checkpoints.length - 1].votingPower.toNumber(), 0);
+  });
+
+  it("28. should return the full stake on emergency exit", async () => {
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+    const userBalanceBefore = await getAccount(
+      provider.connection,
+      adminTokenAccount.address
+    );
+
+    const tx = await program.methods
+      .emergencyExit()
+      .accountsStrict({
+        owner: admin.publicKey,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        stakeVault: stakeVault,
+        userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        vaultTokenAccount: vaultTokenAccount,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    console.log("Emergency exit tx:", tx);
+
+    const userBalanceAfter = await getAccount(
+      provider.connection,
+      adminTokenAccount.address
+    );
+    assert.equal(
+      Number(userBalanceAfter.amount) - Number(userBalanceBefore.amount),
+      userStakeBefore.stakedAmount.toNumber()
+    );
+
+    const userStakeAfter = await program.account.userStake.fetch(userStake);
+    assert.equal(userStakeAfter.stakedAmount.toNumber(), 0);
+    assert.equal(userStakeAfter.openUnstakeRequests.toNumber(), 0);
+
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.stakeStats.totalStaked.toNumber(), 0);
+    assert.equal(vaultState.stakeStats.totalWeightedStake.toNumber(), 0);
+  });
+
+  it("29. should only withdraw the surplus in emergency mode", async () => {
+    const strayAmount = 5_000_000_000;
+
+    // Tokens sent straight to the vault are not owed to anyone
+    await transfer(
+      provider.connection,
+      (admin as any).payer,
+      adminTokenAccount.address,
+      vaultTokenAccount,
+      admin.publicKey,
+      strayAmount
+    );
+
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+    const vaultBalance = await getAccount(provider.connection, vaultTokenAccount);
+    const surplus =
+      Number(vaultBalance.amount) - vaultState.stakeStats.totalStaked.toNumber();
+
+    try {
+      await program.methods
+        .emergencyWithdraw({
+          amount: new BN(surplus + 1),
+        })
+        .accountsStrict({
+          admin: admin.publicKey,
+          stakeVault: stakeVault,
+          sourceTokenAccount: vaultTokenAccount,
+          adminTokenAccount: adminTokenAccount.address,
+          mint: tokenMint,
+          transferAuthority: transferAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "InsufficientVaultBalance");
+    }
+
+    await program.methods
+      .emergencyWithdraw({
+        amount: new BN(0), // 0 means withdraw the whole surplus
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+        sourceTokenAccount: vaultTokenAccount,
+        adminTokenAccount: adminTokenAccount.address,
+        mint: tokenMint,
+        transferAuthority: transferAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const vaultBalanceAfter = await getAccount(provider.connection, vaultTokenAccount);
+    assert.equal(
+      Number(vaultBalanceAfter.amount),
+      vaultState.stakeStats.totalStaked.toNumber()
+    );
+  });
+
+  it("30. should set and clear the voting power retention slot", async () => {
+    const slot = await provider.connection.getSlot();
+
+    await program.methods
+      .updateVotingPowerRetention({ retentionSlot: new BN(slot) })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    let vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.votingPowerRetentionSlot.toNumber(), slot);
+
+    await program.methods
+      .updateVotingPowerRetention({ retentionSlot: null })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.isNull(vaultState.votingPowerRetentionSlot);
+  });
 });
@@ -7,8 +7,16 @@ import {
   TOKEN_PROGRAM_ID,
   mintTo,
   getAccount,
+  getMint,
+  transfer,
 } from "@solana/spl-token";
-import { PublicKey, SystemProgram } from "@solana/web3.js";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
 import { assert } from "chai";
 
 describe("linear-staking", () => {
@@ -20,9 +28,12 @@ describe("linear-staking", () => {
 
   // Test accounts
   let tokenMint: PublicKey;
+  let rewardMint: PublicKey;
   let adminTokenAccount: any;
+  let adminRewardTokenAccount: any;
   let stakeVault: PublicKey;
   let vaultTokenAccount: PublicKey;
+  let rewardTokenAccount: PublicKey;
   let transferAuthority: PublicKey;
   let userStake: PublicKey;
   let eventAuthority: PublicKey;
@@ -33,9 +44,12 @@ describe("linear-staking", () => {
   // Constants (matching Rust seeds)
   const STAKE_VAULT_SEED = Buffer.from("stake_vault");
   const STAKE_VAULT_TOKEN_ACCOUNT_SEED = Buffer.from("stake_vault_token_account");
+  const REWARD_TOKEN_ACCOUNT_SEED = Buffer.from("reward_token_account");
   const USER_STAKE_SEED = Buffer.from("user_stake");
   const TRANSFER_AUTHORITY_SEED = Buffer.from("transfer_authority");
   const EVENT_AUTHORITY_SEED = Buffer.from("__event_authority");
+  const UNSTAKE_REQUEST_SEED = Buffer.from("unstake_request");
+  const VOTING_POWER_SEED = Buffer.from("voting_power");
 
   // Test amounts
   const INITIAL_MINT_AMOUNT = 1_000_000_000_000; // 1000 tokens (9 decimals)
@@ -46,11 +60,65 @@ describe("linear-staking", () => {
   // Short vesting period for testing (10 seconds)
   const TEST_VESTING_PERIOD = 10;
 
+  // Short reward streaming period for testing (5 seconds)
+  const TEST_REWARD_DURATION = 5;
+
   // Helper function
   const sleep = (ms: number): Promise<void> => {
     return new Promise((resolve) => setTimeout(resolve, ms));
   };
 
+  // Requests are seeded by the generation of their user stake, so a re-created stake gets new addresses
+  const requestPdaOf = (stake: PublicKey, generation: BN | number, requestId: BN | number): PublicKey =>
+    PublicKey.findProgramAddressSync(
+      [
+        UNSTAKE_REQUEST_SEED,
+        stake.toBuffer(),
+        new BN(generation).toArrayLike(Buffer, "le", 8),
+        new BN(requestId).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+  // Generation of a user stake, or the one it will get if it does not exist yet
+  const generationOf = async (stake: PublicKey): Promise<BN> => {
+    const userStakeState = await program.account.userStake.fetchNullable(stake);
+    if (userStakeState) {
+      return userStakeState.generation;
+    }
+    return (await program.account.stakeVault.fetch(stakeVault)).userStakesCreated;
+  };
+
+  const unstakeRequestPda = async (requestId: BN | number): Promise<PublicKey> =>
+    requestPdaOf(userStake, await generationOf(userStake), requestId);
+
+  // Voting power record checkpointed on every stake change
+  const votingPowerPda = (stake: PublicKey): PublicKey =>
+    PublicKey.findProgramAddressSync([VOTING_POWER_SEED, stake.toBuffer()], program.programId)[0];
+
+  // PDA the next unstake request of the user will be created at
+  const nextUnstakeRequestPda = async (): Promise<PublicKey> => {
+    const userStakeState = await program.account.userStake.fetch(userStake);
+    return requestPdaOf(userStake, userStakeState.generation, userStakeState.unstakeRequestCount);
+  };
+
+  // Open unstake requests of the user, oldest first
+  const fetchUnstakeRequests = async () => {
+    const requests = await program.account.unstakeRequest.all([
+      { memcmp: { offset: 8, bytes: userStake.toBase58() } },
+    ]);
+    return requests
+      .map((request) => ({ publicKey: request.publicKey, ...request.account }))
+      .sort((a, b) => a.id.cmp(b.id));
+  };
+
+  const requestAccounts = (requests: { publicKey: PublicKey }[]) =>
+    requests.map((request) => ({
+      pubkey: request.publicKey,
+      isWritable: true,
+      isSigner: false,
+    }));
+
   before(async () => {
     // Create token mint
     tokenMint = await createMint(
@@ -78,24 +146,54 @@ describe("linear-staking", () => {
       INITIAL_MINT_AMOUNT * 2 // Double for both admin and user operations
     );
 
+    // Rewards are paid in a separate mint
+    rewardMint = await createMint(
+      provider.connection,
+      (admin as any).payer,
+      admin.publicKey,
+      null,
+      9
+    );
+
+    adminRewardTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      rewardMint,
+      admin.publicKey
+    );
+
+    await mintTo(
+      provider.connection,
+      (admin as any).payer,
+      rewardMint,
+      adminRewardTokenAccount.address,
+      admin.publicKey,
+      INITIAL_MINT_AMOUNT
+    );
+
     // Derive PDAs
     [stakeVault] = PublicKey.findProgramAddressSync(
-      [STAKE_VAULT_SEED],
+      [STAKE_VAULT_SEED, tokenMint.toBuffer()],
       program.programId
     );
 
     [vaultTokenAccount] = PublicKey.findProgramAddressSync(
-      [STAKE_VAULT_TOKEN_ACCOUNT_SEED],
+      [STAKE_VAULT_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer()],
+      program.programId
+    );
+
+    [rewardTokenAccount] = PublicKey.findProgramAddressSync(
+      [REWARD_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer(), rewardMint.toBuffer()],
       program.programId
     );
 
     [transferAuthority] = PublicKey.findProgramAddressSync(
-      [TRANSFER_AUTHORITY_SEED],
+      [TRANSFER_AUTHORITY_SEED, stakeVault.toBuffer()],
       program.programId
     );
 
     [userStake] = PublicKey.findProgramAddressSync(
-      [USER_STAKE_SEED, admin.publicKey.toBuffer()],
+      [USER_STAKE_SEED, stakeVault.toBuffer(), admin.publicKey.toBuffer()],
       program.programId
     );
 
@@ -109,12 +207,16 @@ describe("linear-staking", () => {
     const tx = await program.methods
       .initialize({
         vestingPeriod: new BN(TEST_VESTING_PERIOD),
+        cliffPeriod: null,
+        vestingSchedule: null,
       })
       .accountsStrict({
         admin: admin.publicKey,
         tokenMint: tokenMint,
+        rewardMint: rewardMint,
         stakeVault: stakeVault,
         vaultTokenAccount: vaultTokenAccount,
+        rewardTokenAccount: rewardTokenAccount,
         transferAuthority: transferAuthority,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
@@ -129,6 +231,8 @@ describe("linear-staking", () => {
     assert.equal(vaultState.isInitialized, true);
     assert.equal(vaultState.admin.toString(), admin.publicKey.toString());
     assert.equal(vaultState.tokenMint.toString(), tokenMint.toString());
+    assert.equal(vaultState.rewardMint.toString(), rewardMint.toString());
+    assert.equal(vaultState.rewardTokenAccount.toString(), rewardTokenAccount.toString());
     assert.equal(vaultState.vestingPeriodSeconds.toNumber(), TEST_VESTING_PERIOD);
     assert.equal(vaultState.permissions.allowDeposits, true);
     assert.equal(vaultState.permissions.allowWithdrawals, true);
@@ -138,13 +242,19 @@ describe("linear-staking", () => {
     const tx = await program.methods
       .depositStake({
         amount: new BN(STAKE_AMOUNT),
+        lockup: null,
       })
       .accountsStrict({
         owner: user.publicKey,
         userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
         stakeVault: stakeVault,
         vaultTokenAccount: vaultTokenAccount,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        transferAuthority: null,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         eventAuthority: eventAuthority,
@@ -171,13 +281,19 @@ describe("linear-staking", () => {
     await program.methods
       .depositStake({
         amount: new BN(additionalAmount),
+        lockup: null,
       })
       .accountsStrict({
         owner: user.publicKey,
         userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
         stakeVault: stakeVault,
         vaultTokenAccount: vaultTokenAccount,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        transferAuthority: null,
         systemProgram: SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         eventAuthority: eventAuthority,
@@ -197,13 +313,19 @@ describe("linear-staking", () => {
       await program.methods
         .depositStake({
           amount: new BN(0),
+          lockup: null,
         })
         .accountsStrict({
           owner: user.publicKey,
           userTokenAccount: adminTokenAccount.address,
+          tokenMint: tokenMint,
           stakeVault: stakeVault,
           vaultTokenAccount: vaultTokenAccount,
           userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          receiptMint: null,
+          userReceiptTokenAccount: null,
+          transferAuthority: null,
           systemProgram: SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
           eventAuthority: eventAuthority,
@@ -226,6 +348,12 @@ describe("linear-staking", () => {
         owner: user.publicKey,
         stakeVault: stakeVault,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: await nextUnstakeRequestPda(),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
         eventAuthority: eventAuthority,
         program: program.programId,
       })
@@ -234,12 +362,17 @@ describe("linear-staking", () => {
     console.log("Unstake request tx:", tx);
 
     const userStakeState = await program.account.userStake.fetch(userStake);
-    assert.equal(userStakeState.unstakeRequests.length, 1);
+    assert.equal(userStakeState.openUnstakeRequests.toNumber(), 1);
+    assert.equal(userStakeState.unstakingAmount.toNumber(), UNSTAKE_AMOUNT);
+
+    const unstakeRequests = await fetchUnstakeRequests();
+    assert.equal(unstakeRequests.length, 1);
+    assert.equal(unstakeRequests[0].totalAmount.toNumber(), UNSTAKE_AMOUNT);
+    assert.equal(unstakeRequests[0].claimedAmount.toNumber(), 0);
     assert.equal(
-      userStakeState.unstakeRequests[0].totalAmount.toNumber(),
-      UNSTAKE_AMOUNT
+      unstakeRequests[0].vestingPeriodSeconds.toNumber(),
+      TEST_VESTING_PERIOD
     );
-    assert.equal(userStakeState.unstakeRequests[0].claimedAmount.toNumber(), 0);
 
     const vaultState = await program.account.stakeVault.fetch(stakeVault);
     assert.equal(vaultState.stakeStats.unstakingAmount.toNumber(), UNSTAKE_AMOUNT);
@@ -255,6 +388,12 @@ describe("linear-staking", () => {
           owner: user.publicKey,
           stakeVault: stakeVault,
           userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          unstakeRequest: await nextUnstakeRequestPda(),
+          receiptMint: null,
+          userReceiptTokenAccount: null,
+          tokenProgram: null,
+          systemProgram: SystemProgram.programId,
           eventAuthority: eventAuthority,
           program: program.programId,
         })
@@ -277,12 +416,14 @@ describe("linear-staking", () => {
     );
 
     const tx = await program.methods
-      .claimVested()
+      .claimVested({ maxAmount: null })
       .accountsStrict({
+        caller: user.publicKey,
         owner: user.publicKey,
         userStake: userStake,
         stakeVault: stakeVault,
         userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
         vaultTokenAccount: vaultTokenAccount,
         transferAuthority: transferAuthority,
         systemProgram: SystemProgram.programId,
@@ -290,6 +431,7 @@ describe("linear-staking", () => {
         eventAuthority: eventAuthority,
         program: program.programId,
       })
+      .remainingAccounts(requestAccounts(await fetchUnstakeRequests()))
       .rpc();
 
     console.log("Claim vested tx:", tx);
@@ -310,12 +452,14 @@ describe("linear-staking", () => {
     await sleep(6000);
 
     const tx = await program.methods
-      .claimVested()
+      .claimVested({ maxAmount: null })
       .accountsStrict({
+        caller: user.publicKey,
         owner: user.publicKey,
         userStake: userStake,
         stakeVault: stakeVault,
         userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
         vaultTokenAccount: vaultTokenAccount,
         transferAuthority: transferAuthority,
         systemProgram: SystemProgram.programId,
@@ -323,15 +467,17 @@ describe("linear-staking", () => {
         eventAuthority: eventAuthority,
         program: program.programId,
       })
+      .remainingAccounts(requestAccounts(await fetchUnstakeRequests()))
       .rpc();
 
     console.log("Claim remaining vested tx:", tx);
 
     const userStakeStateAfter = await program.account.userStake.fetch(userStake);
+    assert.equal(userStakeStateAfter.openUnstakeRequests.toNumber(), 0);
     assert.equal(
-      userStakeStateAfter.unstakeRequests.length,
+      (await fetchUnstakeRequests()).length,
       0,
-      "Fully claimed request should be removed"
+      "Fully claimed request should be closed"
     );
   });
 
@@ -345,6 +491,12 @@ describe("linear-staking", () => {
         owner: user.publicKey,
         stakeVault: stakeVault,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: await nextUnstakeRequestPda(),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
         eventAuthority: eventAuthority,
         program: program.programId,
       })
@@ -352,16 +504,21 @@ describe("linear-staking", () => {
 
     const userStakeStateBefore = await program.account.userStake.fetch(userStake);
     const activeStakeBefore = userStakeStateBefore.activeStakeAmount.toNumber();
+    const [request] = await fetchUnstakeRequests();
 
     // Cancel the unstake request
     const tx = await program.methods
       .cancelUnstake({
-        requestIndex: 0,
+        requestId: request.id,
+        amount: null,
       })
       .accountsStrict({
         owner: user.publicKey,
         stakeVault: stakeVault,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: request.publicKey,
+        systemProgram: SystemProgram.programId,
         eventAuthority: eventAuthority,
         program: program.programId,
       })
@@ -375,19 +532,27 @@ describe("linear-staking", () => {
       userStakeStateAfter.activeStakeAmount.toNumber(),
       activeStakeBefore + UNSTAKE_AMOUNT
     );
-    assert.equal(userStakeStateAfter.unstakeRequests.length, 0);
+    assert.equal(userStakeStateAfter.openUnstakeRequests.toNumber(), 0);
+    assert.isNull(
+      await program.account.unstakeRequest.fetchNullable(request.publicKey),
+      "Cancelled request should be closed"
+    );
   });
 
-  it("10. should fail cancel with invalid request index", async () => {
+  it("10. should fail cancel with invalid request id", async () => {
     try {
       await program.methods
         .cancelUnstake({
-          requestIndex: 99,
+          requestId: new BN(99),
+          amount: null,
         })
         .accountsStrict({
           owner: user.publicKey,
           stakeVault: stakeVault,
           userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          unstakeRequest: await unstakeRequestPda(99),
+          systemProgram: SystemProgram.programId,
           eventAuthority: eventAuthority,
           program: program.programId,
         })
@@ -395,7 +560,7 @@ describe("linear-staking", () => {
 
       assert.fail("Should have thrown an error");
     } catch (error: any) {
-      assert.include(error.message, "InvalidRequestIndex");
+      assert.include(error.message, "AccountNotInitialized");
     }
   });
 
@@ -403,12 +568,14 @@ describe("linear-staking", () => {
     const tx = await program.methods
       .depositRewards({
         amount: new BN(REWARD_AMOUNT),
+        durationSeconds: new BN(TEST_REWARD_DURATION),
       })
       .accountsStrict({
         admin: admin.publicKey,
-        adminTokenAccount: adminTokenAccount.address,
+        adminTokenAccount: adminRewardTokenAccount.address,
         stakeVault: stakeVault,
-        vaultTokenAccount: vaultTokenAccount,
+        rewardTokenAccount: rewardTokenAccount,
+        rewardMint: rewardMint,
         tokenProgram: TOKEN_PROGRAM_ID,
         eventAuthority: eventAuthority,
         program: program.programId,
@@ -419,12 +586,21 @@ describe("linear-staking", () => {
 
     const vaultState = await program.account.stakeVault.fetch(stakeVault);
     assert.equal(
-      vaultState.rewardState.pendingRewards.toNumber(),
+      vaultState.rewardStreams[0].pendingRewards.toNumber(),
       REWARD_AMOUNT
     );
+    assert.equal(
+      vaultState.rewardStreams[0].periodFinish.toNumber() -
+        vaultState.rewardStreams[0].lastUpdateTime.toNumber(),
+      TEST_REWARD_DURATION
+    );
   });
 
   it("12. should distribute rewards", async () => {
+    // Wait for the reward period to fully stream out
+    console.log("Waiting 6 seconds for rewards to stream...");
+    await sleep(6000);
+
     const tx = await program.methods
       .distributeRewards()
       .accountsStrict({
@@ -438,14 +614,14 @@ describe("linear-staking", () => {
     console.log("Distribute rewards tx:", tx);
 
     const vaultState = await program.account.stakeVault.fetch(stakeVault);
-    assert.equal(vaultState.rewardState.pendingRewards.toNumber(), 0);
+    assert.equal(vaultState.rewardStreams[0].pendingRewards.toNumber(), 0);
     assert.isAbove(
-      Number(vaultState.rewardState.rewardPerTokenStaked),
+      Number(vaultState.rewardStreams[0].rewardPerTokenStaked),
       0,
       "reward_per_token_staked should be updated"
     );
     assert.equal(
-      Number(vaultState.rewardState.totalDistributed),
+      Number(vaultState.rewardStreams[0].totalDistributed),
       REWARD_AMOUNT
     );
   });
@@ -453,17 +629,19 @@ describe("linear-staking", () => {
   it("13. should collect rewards", async () => {
     const userBalanceBefore = await getAccount(
       provider.connection,
-      adminTokenAccount.address
+      adminRewardTokenAccount.address
     );
 
     const tx = await program.methods
       .collectRewards()
       .accountsStrict({
+        caller: user.publicKey,
         owner: user.publicKey,
         userStake: userStake,
         stakeVault: stakeVault,
-        userTokenAccount: adminTokenAccount.address,
-        vaultTokenAccount: vaultTokenAccount,
+        userTokenAccount: adminRewardTokenAccount.address,
+        rewardTokenAccount: rewardTokenAccount,
+        rewardMint: rewardMint,
         transferAuthority: transferAuthority,
         tokenProgram: TOKEN_PROGRAM_ID,
         eventAuthority: eventAuthority,
@@ -475,7 +653,7 @@ describe("linear-staking", () => {
 
     const userBalanceAfter = await getAccount(
       provider.connection,
-      adminTokenAccount.address
+      adminRewardTokenAccount.address
     );
 
     const rewardsClaimed =
@@ -485,7 +663,7 @@ describe("linear-staking", () => {
 
     const userStakeState = await program.account.userStake.fetch(userStake);
     assert.equal(
-      userStakeState.rewardState.unclaimedRewards.toNumber(),
+      userStakeState.rewardStreams[0].unclaimedRewards.toNumber(),
       0,
       "Unclaimed rewards should be zero"
     );
@@ -496,11 +674,13 @@ describe("linear-staking", () => {
       await program.methods
         .collectRewards()
         .accountsStrict({
+          caller: user.publicKey,
           owner: user.publicKey,
           userStake: userStake,
           stakeVault: stakeVault,
-          userTokenAccount: adminTokenAccount.address,
-          vaultTokenAccount: vaultTokenAccount,
+          userTokenAccount: adminRewardTokenAccount.address,
+          rewardTokenAccount: rewardTokenAccount,
+          rewardMint: rewardMint,
           transferAuthority: transferAuthority,
           tokenProgram: TOKEN_PROGRAM_ID,
           eventAuthority: eventAuthority,
@@ -524,6 +704,12 @@ describe("linear-staking", () => {
         owner: user.publicKey,
         stakeVault: stakeVault,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: await nextUnstakeRequestPda(),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
         eventAuthority: eventAuthority,
         program: program.programId,
       })
@@ -535,13 +721,1869 @@ describe("linear-staking", () => {
         owner: user.publicKey,
         stakeVault: stakeVault,
         userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: await nextUnstakeRequestPda(),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
         eventAuthority: eventAuthority,
         program: program.programId,
       })
       .rpc();
 
     const userStakeState = await program.account.userStake.fetch(userStake);
-    assert.equal(userStakeState.unstakeRequests.length, 2);
+    assert.equal(userStakeState.openUnstakeRequests.toNumber(), 2);
     console.log("Created 2 unstake requests successfully");
   });
+
+  it("16. should host an independent vault for a second mint", async () => {
+    const secondMint = await createMint(
+      provider.connection,
+      (admin as any).payer,
+      admin.publicKey,
+      null,
+      9
+    );
+
+    const secondTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      secondMint,
+      admin.publicKey
+    );
+
+    await mintTo(
+      provider.connection,
+      (admin as any).payer,
+      secondMint,
+      secondTokenAccount.address,
+      admin.publicKey,
+      INITIAL_MINT_AMOUNT
+    );
+
+    const [secondVault] = PublicKey.findProgramAddressSync(
+      [STAKE_VAULT_SEED, secondMint.toBuffer()],
+      program.programId
+    );
+    const [secondVaultTokenAccount] = PublicKey.findProgramAddressSync(
+      [STAKE_VAULT_TOKEN_ACCOUNT_SEED, secondVault.toBuffer()],
+      program.programId
+    );
+    const [secondRewardTokenAccount] = PublicKey.findProgramAddressSync(
+      [REWARD_TOKEN_ACCOUNT_SEED, secondVault.toBuffer(), secondMint.toBuffer()],
+      program.programId
+    );
+    const [secondTransferAuthority] = PublicKey.findProgramAddressSync(
+      [TRANSFER_AUTHORITY_SEED, secondVault.toBuffer()],
+      program.programId
+    );
+    const [secondUserStake] = PublicKey.findProgramAddressSync(
+      [USER_STAKE_SEED, secondVault.toBuffer(), user.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .initialize({
+        vestingPeriod: new BN(TEST_VESTING_PERIOD),
+        cliffPeriod: null,
+        vestingSchedule: null,
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        tokenMint: secondMint,
+        rewardMint: secondMint,
+        stakeVault: secondVault,
+        vaultTokenAccount: secondVaultTokenAccount,
+        rewardTokenAccount: secondRewardTokenAccount,
+        transferAuthority: secondTransferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    await program.methods
+      .depositStake({
+        amount: new BN(STAKE_AMOUNT),
+        lockup: null,
+      })
+      .accountsStrict({
+        owner: user.publicKey,
+        userTokenAccount: secondTokenAccount.address,
+        tokenMint: secondMint,
+        stakeVault: secondVault,
+        vaultTokenAccount: secondVaultTokenAccount,
+        userStake: secondUserStake,
+        votingPowerRecord: votingPowerPda(secondUserStake),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        transferAuthority: null,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const secondVaultState = await program.account.stakeVault.fetch(secondVault);
+    assert.equal(secondVaultState.tokenMint.toString(), secondMint.toString());
+    assert.equal(secondVaultState.stakeStats.totalStaked.toNumber(), STAKE_AMOUNT);
+
+    const secondUserStakeState = await program.account.userStake.fetch(secondUserStake);
+    assert.equal(secondUserStakeState.stakeVault.toString(), secondVault.toString());
+    assert.equal(secondUserStakeState.activeStakeAmount.toNumber(), STAKE_AMOUNT);
+
+    // The first vault's position is untouched
+    const userStakeState = await program.account.userStake.fetch(userStake);
+    assert.equal(userStakeState.stakeVault.toString(), stakeVault.toString());
+  });
+
+  it("17. should pay out multiple reward streams in one collect", async () => {
+    const partnerMint = await createMint(
+      provider.connection,
+      (admin as any).payer,
+      admin.publicKey,
+      null,
+      9
+    );
+
+    const adminPartnerTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      partnerMint,
+      admin.publicKey
+    );
+
+    await mintTo(
+      provider.connection,
+      (admin as any).payer,
+      partnerMint,
+      adminPartnerTokenAccount.address,
+      admin.publicKey,
+      INITIAL_MINT_AMOUNT
+    );
+
+    const [partnerRewardTokenAccount] = PublicKey.findProgramAddressSync(
+      [REWARD_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer(), partnerMint.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .addRewardStream()
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+        rewardMint: partnerMint,
+        rewardTokenAccount: partnerRewardTokenAccount,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultState.rewardStreams.length, 2);
+    assert.equal(vaultState.rewardStreams[1].mint.toString(), partnerMint.toString());
+
+    // Fund both streams and distribute
+    for (const [funding, rewardAccount, rewardMintKey] of [
+      [adminRewardTokenAccount.address, rewardTokenAccount, rewardMint],
+      [adminPartnerTokenAccount.address, partnerRewardTokenAccount, partnerMint],
+    ]) {
+      await program.methods
+        .depositRewards({
+          amount: new BN(REWARD_AMOUNT),
+          durationSeconds: new BN(TEST_REWARD_DURATION),
+        })
+        .accountsStrict({
+          admin: admin.publicKey,
+          adminTokenAccount: funding,
+          stakeVault: stakeVault,
+          rewardTokenAccount: rewardAccount,
+          rewardMint: rewardMintKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+    }
+
+    console.log("Waiting 6 seconds for rewards to stream...");
+    await sleep(6000);
+
+    await program.methods
+      .distributeRewards()
+      .accountsStrict({
+        payer: admin.publicKey,
+        stakeVault: stakeVault,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const partnerBalanceBefore = await getAccount(
+      provider.connection,
+      adminPartnerTokenAccount.address
+    );
+
+    // Primary stream via named accounts, partner stream via remaining accounts
+    await program.methods
+      .collectRewards()
+      .accountsStrict({
+        caller: user.publicKey,
+        owner: user.publicKey,
+        userStake: userStake,
+        stakeVault: stakeVault,
+        userTokenAccount: adminRewardTokenAccount.address,
+        rewardTokenAccount: rewardTokenAccount,
+        rewardMint: rewardMint,
+        transferAuthority: transferAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts([
+        { pubkey: partnerRewardTokenAccount, isSigner: false, isWritable: true },
+        { pubkey: adminPartnerTokenAccount.address, isSigner: false, isWritable: true },
+        { pubkey: partnerMint, isSigner: false, isWritable: false },
+      ])
+      .rpc();
+
+    const partnerBalanceAfter = await getAccount(
+      provider.connection,
+      adminPartnerTokenAccount.address
+    );
+    assert.isAbove(
+      Number(partnerBalanceAfter.amount) - Number(partnerBalanceBefore.amount),
+      0,
+      "Should have collected partner rewards"
+    );
+
+    const userStakeState = await program.account.userStake.fetch(userStake);
+    assert.equal(userStakeState.rewardStreams.length, 2);
+    assert.equal(userStakeState.rewardStreams[0].unclaimedRewards.toNumber(), 0);
+    assert.equal(userStakeState.rewardStreams[1].unclaimedRewards.toNumber(), 0);
+  });
+
+  it("18. should instantly unstake a request minus the early-exit penalty", async () => {
+    const PENALTY_BPS = 5000; // 50% right after the request is created
+
+    // No reward stream is paid in the staked mint yet, so penalties can't be redistributed
+    try {
+      await program.methods
+        .updateEarlyExitConfig({
+          penaltyBps: PENALTY_BPS,
+          treasury: PublicKey.default,
+        })
+        .accountsStrict({
+          admin: admin.publicKey,
+          stakeVault: stakeVault,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "InvalidPenaltyDestination");
+    }
+
+    await program.methods
+      .updateEarlyExitConfig({
+        penaltyBps: PENALTY_BPS,
+        treasury: admin.publicKey,
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+      })
+      .rpc();
+
+    await program.methods
+      .unstakeRequest({ amount: new BN(UNSTAKE_AMOUNT) })
+      .accountsStrict({
+        owner: user.publicKey,
+        stakeVault: stakeVault,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: await nextUnstakeRequestPda(),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+    const requestId = userStakeBefore.unstakeRequestCount.subn(1);
+    const vaultBefore = await program.account.stakeVault.fetch(stakeVault);
+
+    const tx = await program.methods
+      .instantUnstake({ requestId })
+      .accountsStrict({
+        owner: user.publicKey,
+        userStake: userStake,
+        unstakeRequest: await unstakeRequestPda(requestId),
+        stakeVault: stakeVault,
+        userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        vaultTokenAccount: vaultTokenAccount,
+        penaltyTokenAccount: adminTokenAccount.address,
+        transferAuthority: transferAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    console.log("Instant unstake tx:", tx);
+
+    const userStakeAfter = await program.account.userStake.fetch(userStake);
+    assert.equal(
+      userStakeAfter.openUnstakeRequests.toNumber(),
+      userStakeBefore.openUnstakeRequests.toNumber() - 1
+    );
+    assert.equal(
+      userStakeAfter.stakedAmount.toNumber(),
+      userStakeBefore.stakedAmount.toNumber() - UNSTAKE_AMOUNT
+    );
+
+    // Only the released part counts as vested, the penalty went to the treasury
+    const released =
+      userStakeAfter.vestedStakeAmount.toNumber() -
+      userStakeBefore.vestedStakeAmount.toNumber();
+    assert.isBelow(released, UNSTAKE_AMOUNT);
+    assert.isAtLeast(released, UNSTAKE_AMOUNT / 2);
+
+    const vaultAfter = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(
+      vaultAfter.stakeStats.totalStaked.toNumber(),
+      vaultBefore.stakeStats.totalStaked.toNumber() - UNSTAKE_AMOUNT
+    );
+  });
+
+  it("19. should boost reward weight for locked deposits and keep them staked", async () => {
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+
+    await program.methods
+      .depositStake({
+        amount: new BN(STAKE_AMOUNT),
+        lockup: { threeMonths: {} },
+      })
+      .accountsStrict({
+        owner: user.publicKey,
+        userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        stakeVault: stakeVault,
+        vaultTokenAccount: vaultTokenAccount,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        transferAuthority: null,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const userStakeAfter = await program.account.userStake.fetch(userStake);
+    assert.equal(userStakeAfter.stakeLocks.length, 1);
+    assert.equal(
+      userStakeAfter.weightedStakeAmount.toNumber(),
+      userStakeBefore.weightedStakeAmount.toNumber() + STAKE_AMOUNT * 1.25
+    );
+
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(
+      vaultState.stakeStats.totalWeightedStake.toNumber(),
+      userStakeAfter.weightedStakeAmount.toNumber()
+    );
+
+    try {
+      await program.methods
+        .unstakeRequest({ amount: userStakeAfter.activeStakeAmount })
+        .accountsStrict({
+          owner: user.publicKey,
+          stakeVault: stakeVault,
+          userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          unstakeRequest: await nextUnstakeRequestPda(),
+          receiptMint: null,
+          userReceiptTokenAccount: null,
+          tokenProgram: null,
+          systemProgram: SystemProgram.programId,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "StakeLocked");
+    }
+
+    // The boost only leaves the vault's weight once the lock has expired
+    try {
+      await program.methods
+        .expireStakeLocks()
+        .accountsStrict({
+          payer: admin.publicKey,
+          stakeVault: stakeVault,
+          userStake: userStake,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "NoExpiredStakeLocks");
+    }
+  });
+
+  it("20. should checkpoint voting power including the decaying lock boost", async () => {
+    const [votingPowerRecord] = PublicKey.findProgramAddressSync(
+      [VOTING_POWER_SEED, userStake.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .checkpointVotingPower()
+      .accountsStrict({
+        owner: user.publicKey,
+        stakeVault: stakeVault,
+        userStake: userStake,
+        votingPowerRecord: votingPowerRecord,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const userStakeState = await program.account.userStake.fetch(userStake);
+    // Earlier stake changes were checkpointed already, the latest checkpoint is this one
+    const record = await program.account.votingPowerRecord.fetch(votingPowerRecord);
+    assert.isAbove(record.checkpoints.length, 1);
+
+    // The lock was just created, so almost the full boost still counts
+    const votingPower = record.checkpoints[record.checkpoints.length - 1].votingPower.toNumber();
+    assert.isAbove(votingPower, userStakeState.activeStakeAmount.toNumber());
+    assert.isAtMost(votingPower, userStakeState.weightedStakeAmount.toNumber());
+  });
+
+  it("21. should sweep surplus and stray tokens but never principal", async () => {
+    const STRAY_AMOUNT = 1_000_000_000;
+
+    // Tokens of an unrelated mint sent to the vault's authority by mistake
+    const strayMint = await createMint(
+      provider.connection,
+      (admin as any).payer,
+      admin.publicKey,
+      null,
+      9
+    );
+    const strayVaultAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      strayMint,
+      transferAuthority,
+      true
+    );
+    const adminStrayAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      strayMint,
+      admin.publicKey
+    );
+    await mintTo(
+      provider.connection,
+      (admin as any).payer,
+      strayMint,
+      strayVaultAccount.address,
+      admin.publicKey,
+      STRAY_AMOUNT
+    );
+
+    // Only the emergency guardian can move surplus
+    const outsider = Keypair.generate();
+    try {
+      await program.methods
+        .sweepSurplus({ amount: new BN(0) })
+        .accountsStrict({
+          admin: outsider.publicKey,
+          stakeVault: stakeVault,
+          sourceTokenAccount: strayVaultAccount.address,
+          adminTokenAccount: adminStrayAccount.address,
+          mint: strayMint,
+          transferAuthority: transferAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([outsider])
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "Unauthorized");
+    }
+
+    await program.methods
+      .sweepSurplus({ amount: new BN(0) })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+        sourceTokenAccount: strayVaultAccount.address,
+        adminTokenAccount: adminStrayAccount.address,
+        mint: strayMint,
+        transferAuthority: transferAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const adminStrayBalance = await getAccount(
+      provider.connection,
+      adminStrayAccount.address
+    );
+    assert.equal(Number(adminStrayBalance.amount), STRAY_AMOUNT);
+
+    // Staked principal is an obligation, only tokens sent on top of it are surplus
+    await transfer(
+      provider.connection,
+      (admin as any).payer,
+      adminTokenAccount.address,
+      vaultTokenAccount,
+      admin.publicKey,
+      STRAY_AMOUNT
+    );
+
+    const vaultState = await program.account.stakeVault.fetch(stakeVault);
+    const vaultBalance = await getAccount(provider.connection, vaultTokenAccount);
+    const surplus =
+      Number(vaultBalance.amount) - vaultState.stakeStats.totalStaked.toNumber();
+    assert.isAtLeast(surplus, STRAY_AMOUNT);
+
+    try {
+      await program.methods
+        .sweepSurplus({ amount: new BN(surplus + 1) })
+        .accountsStrict({
+          admin: admin.publicKey,
+          stakeVault: stakeVault,
+          sourceTokenAccount: vaultTokenAccount,
+          adminTokenAccount: adminTokenAccount.address,
+          mint: tokenMint,
+          transferAuthority: transferAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "InsufficientVaultBalance");
+    }
+
+    await program.methods
+      .sweepSurplus({ amount: new BN(0) })
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+        sourceTokenAccount: vaultTokenAccount,
+        adminTokenAccount: adminTokenAccount.address,
+        mint: tokenMint,
+        transferAuthority: transferAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const vaultBalanceAfter = await getAccount(provider.connection, vaultTokenAccount);
+    assert.equal(
+      Number(vaultBalanceAfter.amount),
+      vaultState.stakeStats.totalStaked.toNumber()
+    );
+  });
+
+  it("22. should claim a capped amount from a single unstake request", async () => {
+    const CLAIM_CAP = 1_000;
+
+    await program.methods
+      .unstakeRequest({
+        amount: new BN(UNSTAKE_AMOUNT),
+      })
+      .accountsStrict({
+        owner: user.publicKey,
+        stakeVault: stakeVault,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: await nextUnstakeRequestPda(),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    console.log("Waiting 3 seconds for partial vesting...");
+    await sleep(3000);
+
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+    const requestsBefore = await fetchUnstakeRequests();
+    const requestIndex = requestsBefore.length - 1;
+
+    await program.methods
+      .claimVested({
+        maxAmount: new BN(CLAIM_CAP),
+      })
+      .accountsStrict({
+        caller: user.publicKey,
+        owner: user.publicKey,
+        userStake: userStake,
+        stakeVault: stakeVault,
+        userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        vaultTokenAccount: vaultTokenAccount,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts(requestAccounts([requestsBefore[requestIndex]]))
+      .rpc();
+
+    const userStakeAfter = await program.account.userStake.fetch(userStake);
+    const requestsAfter = await fetchUnstakeRequests();
+
+    // Only the passed request moved, by exactly the cap
+    requestsBefore.forEach((request, index) => {
+      const expected =
+        request.claimedAmount.toNumber() + (index === requestIndex ? CLAIM_CAP : 0);
+      assert.equal(requestsAfter[index].claimedAmount.toNumber(), expected);
+    });
+    assert.equal(
+      userStakeAfter.vestedStakeAmount.toNumber(),
+      userStakeBefore.vestedStakeAmount.toNumber() + CLAIM_CAP
+    );
+  });
+
+  it("23. should partially cancel an unstake request", async () => {
+    const requestPda = await nextUnstakeRequestPda();
+
+    await program.methods
+      .unstakeRequest({
+        amount: new BN(UNSTAKE_AMOUNT),
+      })
+      .accountsStrict({
+        owner: user.publicKey,
+        stakeVault: stakeVault,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: requestPda,
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+    const requestBefore = await program.account.unstakeRequest.fetch(requestPda);
+    const cancelAmount = Math.floor(UNSTAKE_AMOUNT / 2);
+    const keptAmount = UNSTAKE_AMOUNT - cancelAmount;
+
+    await program.methods
+      .cancelUnstake({
+        requestId: requestBefore.id,
+        amount: new BN(cancelAmount),
+      })
+      .accountsStrict({
+        owner: user.publicKey,
+        stakeVault: stakeVault,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: requestPda,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const userStakeAfter = await program.account.userStake.fetch(userStake);
+    assert.equal(
+      userStakeAfter.openUnstakeRequests.toNumber(),
+      userStakeBefore.openUnstakeRequests.toNumber()
+    );
+
+    // The rest keeps vesting on the original schedule
+    const requestAfter = await program.account.unstakeRequest.fetch(requestPda);
+    assert.equal(requestAfter.totalAmount.toNumber(), UNSTAKE_AMOUNT);
+    assert.equal(requestAfter.cancelledAmount.toNumber(), cancelAmount);
+    assert.equal(requestAfter.startTime.toNumber(), requestBefore.startTime.toNumber());
+    assert.equal(
+      requestAfter.vestingPeriodSeconds.toNumber(),
+      requestBefore.vestingPeriodSeconds.toNumber()
+    );
+    assert.equal(
+      userStakeAfter.activeStakeAmount.toNumber(),
+      userStakeBefore.activeStakeAmount.toNumber() + cancelAmount
+    );
+
+    // Halfway through vesting only about half of the kept half is unlocked
+    console.log("Waiting 4 seconds for the remainder to half vest...");
+    await sleep(4000);
+
+    await program.methods
+      .claimVested({ maxAmount: null })
+      .accountsStrict({
+        caller: user.publicKey,
+        owner: user.publicKey,
+        userStake: userStake,
+        stakeVault: stakeVault,
+        userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        vaultTokenAccount: vaultTokenAccount,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts(requestAccounts([{ publicKey: requestPda }]))
+      .rpc();
+
+    const userStakeClaimed = await program.account.userStake.fetch(userStake);
+    const claimedAmount =
+      userStakeClaimed.vestedStakeAmount.toNumber() -
+      userStakeAfter.vestedStakeAmount.toNumber();
+    assert.isAtLeast(claimedAmount, keptAmount * 0.3);
+    assert.isAtMost(claimedAmount, keptAmount * 0.75);
+
+    // The request stays open with the unvested rest of the kept half
+    const requestClaimed = await program.account.unstakeRequest.fetch(requestPda);
+    assert.equal(requestClaimed.claimedAmount.toNumber(), claimedAmount);
+  });
+
+  it("24. should give unstake requests stable ids seeded by the user stake's generation", async () => {
+    const userStakeState = await program.account.userStake.fetch(userStake);
+    const requests = await fetchUnstakeRequests();
+    const ids = requests.map((request) => request.id.toNumber());
+
+    // Each request lives at the PDA of the user stake's generation and its id
+    requests.forEach((request) =>
+      assert.isTrue(
+        request.publicKey.equals(requestPdaOf(userStake, userStakeState.generation, request.id))
+      )
+    );
+
+    // Ids keep increasing even though earlier requests were removed
+    ids.slice(1).forEach((id, index) => assert.isAbove(id, ids[index]));
+    assert.isAbove(userStakeState.unstakeRequestCount.toNumber(), Math.max(...ids));
+    assert.isAbove(userStakeState.unstakeRequestCount.toNumber(), ids.length);
+  });
+
+  it("25. should open more unstake requests than the former per-user cap", async () => {
+    const REQUEST_COUNT = 33;
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+    const amount = Math.floor(
+      userStakeBefore.activeStakeAmount.toNumber() / (REQUEST_COUNT * 2)
+    );
+
+    for (let i = 0; i < REQUEST_COUNT; i++) {
+      await program.methods
+        .unstakeRequest({ amount: new BN(amount) })
+        .accountsStrict({
+          owner: user.publicKey,
+          stakeVault: stakeVault,
+          userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          unstakeRequest: await nextUnstakeRequestPda(),
+          receiptMint: null,
+          userReceiptTokenAccount: null,
+          tokenProgram: null,
+          systemProgram: SystemProgram.programId,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+    }
+
+    const userStakeAfter = await program.account.userStake.fetch(userStake);
+    assert.equal(
+      userStakeAfter.openUnstakeRequests.toNumber(),
+      userStakeBefore.openUnstakeRequests.toNumber() + REQUEST_COUNT
+    );
+    assert.equal(
+      userStakeAfter.unstakingAmount.toNumber(),
+      userStakeBefore.unstakingAmount.toNumber() + amount * REQUEST_COUNT
+    );
+    assert.equal(
+      (await fetchUnstakeRequests()).length,
+      userStakeAfter.openUnstakeRequests.toNumber()
+    );
+  });
+
+  it("26. should merge unstake requests without speeding up vesting", async () => {
+    const requests = await fetchUnstakeRequests();
+    const [target, ...merged] = requests.slice(-6);
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+    const vaultBefore = await program.account.stakeVault.fetch(stakeVault);
+
+    const remaining = (request: typeof target) =>
+      request.totalAmount.toNumber() -
+      request.cancelledAmount.toNumber() -
+      request.claimedAmount.toNumber();
+    const latestEnd = Math.max(
+      ...[target, ...merged].map(
+        (request) => request.startTime.toNumber() + request.vestingPeriodSeconds.toNumber()
+      )
+    );
+
+    await program.methods
+      .mergeUnstakeRequests({ requestId: target.id })
+      .accountsStrict({
+        owner: user.publicKey,
+        stakeVault: stakeVault,
+        userStake: userStake,
+        unstakeRequest: target.publicKey,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts(requestAccounts(merged))
+      .rpc();
+
+    const userStakeAfter = await program.account.userStake.fetch(userStake);
+    assert.equal(
+      userStakeAfter.openUnstakeRequests.toNumber(),
+      userStakeBefore.openUnstakeRequests.toNumber() - merged.length
+    );
+    assert.equal(
+      userStakeAfter.unstakingAmount.toNumber(),
+      userStakeBefore.unstakingAmount.toNumber()
+    );
+
+    // The merged request carries the whole remainder and ends with the latest original
+    const mergedRequest = await program.account.unstakeRequest.fetch(target.publicKey);
+    assert.equal(
+      mergedRequest.totalAmount.toNumber(),
+      [target, ...merged].reduce((sum, request) => sum + remaining(request), 0)
+    );
+    assert.equal(mergedRequest.claimedAmount.toNumber(), 0);
+    assert.equal(
+      mergedRequest.startTime.toNumber() + mergedRequest.vestingPeriodSeconds.toNumber(),
+      latestEnd
+    );
+
+    for (const request of merged) {
+      assert.isNull(await program.account.unstakeRequest.fetchNullable(request.publicKey));
+    }
+
+    const vaultAfter = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(
+      vaultAfter.stakeStats.unstakingAmount.toNumber(),
+      vaultBefore.stakeStats.unstakingAmount.toNumber()
+    );
+  });
+
+  it("27. should refuse to merge a request whose claims are ahead of its vesting after a cancel", async () => {
+    const unstakeRequest = async (requestPda: PublicKey) =>
+      program.methods
+        .unstakeRequest({ amount: new BN(UNSTAKE_AMOUNT) })
+        .accountsStrict({
+          owner: user.publicKey,
+          stakeVault: stakeVault,
+          userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          unstakeRequest: requestPda,
+          receiptMint: null,
+          userReceiptTokenAccount: null,
+          tokenProgram: null,
+          systemProgram: SystemProgram.programId,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+
+    const cancelledPda = await nextUnstakeRequestPda();
+    await unstakeRequest(cancelledPda);
+
+    console.log("Waiting 5 seconds for the request to half vest...");
+    await sleep(5000);
+
+    await program.methods
+      .claimVested({ maxAmount: null })
+      .accountsStrict({
+        caller: user.publicKey,
+        owner: user.publicKey,
+        userStake: userStake,
+        stakeVault: stakeVault,
+        userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        vaultTokenAccount: vaultTokenAccount,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts(requestAccounts([{ publicKey: cancelledPda }]))
+      .rpc();
+
+    // Cancelling all but one token of the rest leaves the claims ahead of what now vests
+    const claimed = await program.account.unstakeRequest.fetch(cancelledPda);
+    await program.methods
+      .cancelUnstake({
+        requestId: claimed.id,
+        amount: new BN(UNSTAKE_AMOUNT - claimed.claimedAmount.toNumber() - 1),
+      })
+      .accountsStrict({
+        owner: user.publicKey,
+        stakeVault: stakeVault,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: cancelledPda,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const freshPda = await nextUnstakeRequestPda();
+    await unstakeRequest(freshPda);
+    const fresh = await program.account.unstakeRequest.fetch(freshPda);
+
+    try {
+      await program.methods
+        .mergeUnstakeRequests({ requestId: fresh.id })
+        .accountsStrict({
+          owner: user.publicKey,
+          stakeVault: stakeVault,
+          userStake: userStake,
+          unstakeRequest: freshPda,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .remainingAccounts(requestAccounts([{ publicKey: cancelledPda }]))
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "UnstakeRequestsNotMergeable");
+    }
+
+    // Both requests are left as they were
+    assert.isNotNull(await program.account.unstakeRequest.fetchNullable(cancelledPda));
+    const freshAfter = await program.account.unstakeRequest.fetch(freshPda);
+    assert.equal(freshAfter.totalAmount.toNumber(), UNSTAKE_AMOUNT);
+  });
+
+  it("28. should mint and redeem liquid staking receipts", async () => {
+    const [receiptMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_mint"), stakeVault.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .enableLiquidStaking()
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+        tokenMint: tokenMint,
+        receiptMint: receiptMint,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const userReceiptTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      receiptMint,
+      user.publicKey
+    );
+
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+    const vaultBefore = await program.account.stakeVault.fetch(stakeVault);
+
+    await program.methods
+      .depositStake({ amount: new BN(STAKE_AMOUNT), lockup: null })
+      .accountsStrict({
+        owner: user.publicKey,
+        userTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        stakeVault: stakeVault,
+        vaultTokenAccount: vaultTokenAccount,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        receiptMint: receiptMint,
+        userReceiptTokenAccount: userReceiptTokenAccount.address,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    // First liquid deposit mints receipts 1:1, the user stake itself is untouched
+    const receipts = await getAccount(provider.connection, userReceiptTokenAccount.address);
+    assert.equal(Number(receipts.amount), STAKE_AMOUNT);
+
+    const userStakeAfterDeposit = await program.account.userStake.fetch(userStake);
+    assert.equal(
+      userStakeAfterDeposit.activeStakeAmount.toNumber(),
+      userStakeBefore.activeStakeAmount.toNumber()
+    );
+
+    const vaultAfterDeposit = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultAfterDeposit.liquidPool.pooledAmount.toNumber(), STAKE_AMOUNT);
+    assert.equal(
+      vaultAfterDeposit.stakeStats.activeAmount.toNumber(),
+      vaultBefore.stakeStats.activeAmount.toNumber() + STAKE_AMOUNT
+    );
+
+    // Burning half the receipts opens an unstake request for half the pool
+    const redeemed = STAKE_AMOUNT / 2;
+    const unstakeRequest = await nextUnstakeRequestPda();
+
+    await program.methods
+      .unstakeRequest({ amount: new BN(redeemed) })
+      .accountsStrict({
+        owner: user.publicKey,
+        stakeVault: stakeVault,
+        userStake: userStake,
+        votingPowerRecord: votingPowerPda(userStake),
+        unstakeRequest: unstakeRequest,
+        receiptMint: receiptMint,
+        userReceiptTokenAccount: userReceiptTokenAccount.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const receiptsAfter = await getAccount(provider.connection, userReceiptTokenAccount.address);
+    assert.equal(Number(receiptsAfter.amount), STAKE_AMOUNT - redeemed);
+
+    const request = await program.account.unstakeRequest.fetch(unstakeRequest);
+    assert.equal(request.totalAmount.toNumber(), redeemed);
+
+    const vaultAfter = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultAfter.liquidPool.pooledAmount.toNumber(), STAKE_AMOUNT - redeemed);
+  });
+
+  it("29. should compound staked-mint rewards into active stake", async () => {
+    const [stakeRewardTokenAccount] = PublicKey.findProgramAddressSync(
+      [REWARD_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer(), tokenMint.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .addRewardStream()
+      .accountsStrict({
+        admin: admin.publicKey,
+        stakeVault: stakeVault,
+        rewardMint: tokenMint,
+        rewardTokenAccount: stakeRewardTokenAccount,
+        transferAuthority: transferAuthority,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    await program.methods
+      .depositRewards({
+        amount: new BN(REWARD_AMOUNT),
+        durationSeconds: new BN(TEST_REWARD_DURATION),
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        adminTokenAccount: adminTokenAccount.address,
+        stakeVault: stakeVault,
+        rewardTokenAccount: stakeRewardTokenAccount,
+        rewardMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    console.log("Waiting 3 seconds for rewards to stream...");
+    await sleep(3000);
+
+    const compound = (caller: Keypair | null) =>
+      program.methods
+        .compoundRewards()
+        .accountsStrict({
+          caller: caller ? caller.publicKey : user.publicKey,
+          userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          stakeVault: stakeVault,
+          tokenMint: tokenMint,
+          rewardTokenAccount: stakeRewardTokenAccount,
+          vaultTokenAccount: vaultTokenAccount,
+          transferAuthority: transferAuthority,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers(caller ? [caller] : [])
+        .rpc();
+
+    // A crank cannot compound until the owner opts in
+    const crank = Keypair.generate();
+    try {
+      await compound(crank);
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "Unauthorized");
+    }
+
+    await program.methods
+      .setAutoCompound({ enabled: true })
+      .accountsStrict({
+        owner: user.publicKey,
+        userStake: userStake,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const userStakeBefore = await program.account.userStake.fetch(userStake);
+    const vaultBefore = await program.account.stakeVault.fetch(stakeVault);
+
+    await compound(crank);
+
+    const userStakeAfter = await program.account.userStake.fetch(userStake);
+    const vaultAfter = await program.account.stakeVault.fetch(stakeVault);
+    const streamIndex = vaultAfter.rewardStreams.findIndex((stream) =>
+      stream.mint.equals(tokenMint)
+    );
+    const compounded =
+      userStakeAfter.rewardStreams[streamIndex].totalClaimed.toNumber() -
+      (userStakeBefore.rewardStreams[streamIndex]?.totalClaimed.toNumber() ?? 0);
+
+    assert.isAbove(compounded, 0);
+    assert.equal(userStakeAfter.rewardStreams[streamIndex].unclaimedRewards.toNumber(), 0);
+    assert.equal(
+      userStakeAfter.activeStakeAmount.toNumber(),
+      userStakeBefore.activeStakeAmount.toNumber() + compounded
+    );
+    assert.isAtLeast(
+      vaultAfter.stakeStats.activeAmount.toNumber(),
+      vaultBefore.stakeStats.activeAmount.toNumber() + compounded
+    );
+  });
+
+  it("30. should stake on behalf of another wallet", async () => {
+    const beneficiary = Keypair.generate();
+    const [beneficiaryStake] = PublicKey.findProgramAddressSync(
+      [USER_STAKE_SEED, stakeVault.toBuffer(), beneficiary.publicKey.toBuffer()],
+      program.programId
+    );
+    const payerStakeBefore = await program.account.userStake.fetch(userStake);
+
+    await program.methods
+      .depositStakeFor({ amount: new BN(STAKE_AMOUNT) })
+      .accountsStrict({
+        payer: admin.publicKey,
+        beneficiary: beneficiary.publicKey,
+        payerTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        stakeVault: stakeVault,
+        vaultTokenAccount: vaultTokenAccount,
+        userStake: beneficiaryStake,
+        votingPowerRecord: votingPowerPda(beneficiaryStake),
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    // The stake belongs to the beneficiary, the payer's own position is untouched
+    const beneficiaryState = await program.account.userStake.fetch(beneficiaryStake);
+    assert.isTrue(beneficiaryState.owner.equals(beneficiary.publicKey));
+    assert.equal(beneficiaryState.activeStakeAmount.toNumber(), STAKE_AMOUNT);
+    // Payers cannot lock a beneficiary's stake
+    assert.lengthOf(beneficiaryState.stakeLocks, 0);
+
+    const payerStakeAfter = await program.account.userStake.fetch(userStake);
+    assert.equal(
+      payerStakeAfter.activeStakeAmount.toNumber(),
+      payerStakeBefore.activeStakeAmount.toNumber()
+    );
+  });
+
+  it("31. should transfer a whole position to another wallet", async () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+    const stakeOf = (owner: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [USER_STAKE_SEED, stakeVault.toBuffer(), owner.toBuffer()],
+        program.programId
+      )[0];
+    const requestOf = async (owner: PublicKey, requestId: number) =>
+      requestPdaOf(stakeOf(owner), await generationOf(stakeOf(owner)), requestId);
+
+    // The seller pays rent for the accounts created along the way
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: seller.publicKey,
+          lamports: LAMPORTS_PER_SOL / 10,
+        })
+      )
+    );
+
+    await program.methods
+      .depositStakeFor({ amount: new BN(STAKE_AMOUNT) })
+      .accountsStrict({
+        payer: admin.publicKey,
+        beneficiary: seller.publicKey,
+        payerTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        stakeVault: stakeVault,
+        vaultTokenAccount: vaultTokenAccount,
+        userStake: stakeOf(seller.publicKey),
+        votingPowerRecord: votingPowerPda(stakeOf(seller.publicKey)),
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    await program.methods
+      .unstakeRequest({ amount: new BN(UNSTAKE_AMOUNT) })
+      .accountsStrict({
+        owner: seller.publicKey,
+        stakeVault: stakeVault,
+        userStake: stakeOf(seller.publicKey),
+        votingPowerRecord: votingPowerPda(stakeOf(seller.publicKey)),
+        unstakeRequest: await requestOf(seller.publicKey, 0),
+        receiptMint: null,
+        userReceiptTokenAccount: null,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .signers([seller])
+      .rpc();
+
+    // Lamports sent to the buyer's request address beforehand must not block the transfer
+    const buyerRequest = await requestOf(buyer.publicKey, 0);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: buyerRequest,
+          lamports: LAMPORTS_PER_SOL / 1000,
+        })
+      )
+    );
+
+    const vaultBefore = await program.account.stakeVault.fetch(stakeVault);
+
+    await program.methods
+      .transferPosition()
+      .accountsStrict({
+        owner: seller.publicKey,
+        recipient: buyer.publicKey,
+        stakeVault: stakeVault,
+        userStake: stakeOf(seller.publicKey),
+        votingPowerRecord: votingPowerPda(stakeOf(seller.publicKey)),
+        recipientStake: stakeOf(buyer.publicKey),
+        recipientVotingPowerRecord: votingPowerPda(stakeOf(buyer.publicKey)),
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .remainingAccounts(
+        [await requestOf(seller.publicKey, 0), await requestOf(buyer.publicKey, 0)].map((pubkey) => ({
+          pubkey,
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .signers([seller, buyer])
+      .rpc();
+
+    const sellerState = await program.account.userStake.fetch(stakeOf(seller.publicKey));
+    assert.equal(sellerState.stakedAmount.toNumber(), 0);
+    assert.equal(sellerState.openUnstakeRequests.toNumber(), 0);
+    assert.isNull(
+      await program.account.unstakeRequest.fetchNullable(await requestOf(seller.publicKey, 0))
+    );
+
+    const buyerState = await program.account.userStake.fetch(stakeOf(buyer.publicKey));
+    assert.isTrue(buyerState.owner.equals(buyer.publicKey));
+    assert.equal(buyerState.stakedAmount.toNumber(), STAKE_AMOUNT);
+    assert.equal(buyerState.activeStakeAmount.toNumber(), STAKE_AMOUNT - UNSTAKE_AMOUNT);
+    assert.equal(buyerState.openUnstakeRequests.toNumber(), 1);
+
+    const movedRequest = await program.account.unstakeRequest.fetch(
+      await requestOf(buyer.publicKey, 0)
+    );
+    assert.equal(movedRequest.totalAmount.toNumber(), UNSTAKE_AMOUNT);
+    assert.isTrue(movedRequest.userStake.equals(stakeOf(buyer.publicKey)));
+
+    // The pre-funded address was topped up to rent exemption and taken over by the program
+    const movedRequestInfo = await provider.connection.getAccountInfo(buyerRequest);
+    assert.isTrue(movedRequestInfo.owner.equals(program.programId));
+    assert.equal(
+      movedRequestInfo.lamports,
+      await provider.connection.getMinimumBalanceForRentExemption(movedRequestInfo.data.length)
+    );
+
+    // Voting power is checkpointed on both sides in the same transaction
+    const latestVotingPower = async (owner: PublicKey) => {
+      const record = await program.account.votingPowerRecord.fetch(votingPowerPda(stakeOf(owner)));
+      return record.checkpoints[record.checkpoints.length - 1].votingPower.toNumber();
+    };
+    assert.equal(await latestVotingPower(seller.publicKey), 0);
+    assert.equal(await latestVotingPower(buyer.publicKey), STAKE_AMOUNT - UNSTAKE_AMOUNT);
+
+    // Moving a position leaves the vault totals unchanged
+    const vaultAfter = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(
+      vaultAfter.stakeStats.totalWeightedStake.toNumber(),
+      vaultBefore.stakeStats.totalWeightedStake.toNumber()
+    );
+  });
+
+  it("32. should let a delegate collect rewards to the owner's account", async () => {
+    const delegate = Keypair.generate();
+
+    await program.methods
+      .depositRewards({
+        amount: new BN(REWARD_AMOUNT),
+        durationSeconds: new BN(TEST_REWARD_DURATION),
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        adminTokenAccount: adminTokenAccount.address,
+        stakeVault: stakeVault,
+        rewardTokenAccount: stakeRewardTokenAccount,
+        rewardMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    console.log("Waiting 3 seconds for rewards to stream...");
+    await sleep(3000);
+
+    const collect = (caller: Keypair) =>
+      program.methods
+        .collectRewards()
+        .accountsStrict({
+          caller: caller.publicKey,
+          owner: user.publicKey,
+          userStake: userStake,
+          stakeVault: stakeVault,
+          userTokenAccount: adminTokenAccount.address,
+          rewardTokenAccount: stakeRewardTokenAccount,
+          rewardMint: tokenMint,
+          transferAuthority: transferAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([caller])
+        .rpc();
+
+    // Nobody can collect before being made the delegate
+    try {
+      await collect(delegate);
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "Unauthorized");
+    }
+
+    await program.methods
+      .setDelegate({ delegate: delegate.publicKey })
+      .accountsStrict({
+        owner: user.publicKey,
+        userStake: userStake,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const balanceBefore = await getAccount(provider.connection, adminTokenAccount.address);
+    await collect(delegate);
+    const balanceAfter = await getAccount(provider.connection, adminTokenAccount.address);
+    assert.isTrue(balanceAfter.amount > balanceBefore.amount);
+
+    // The delegate cannot move the stake itself
+    try {
+      await program.methods
+        .unstakeRequest({ amount: new BN(UNSTAKE_AMOUNT) })
+        .accountsStrict({
+          owner: delegate.publicKey,
+          stakeVault: stakeVault,
+          userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          unstakeRequest: await nextUnstakeRequestPda(),
+          receiptMint: null,
+          userReceiptTokenAccount: null,
+          tokenProgram: null,
+          systemProgram: SystemProgram.programId,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .signers([delegate])
+        .rpc();
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "ConstraintSeeds");
+    }
+
+    await program.methods
+      .setDelegate({ delegate: null })
+      .accountsStrict({
+        owner: user.publicKey,
+        userStake: userStake,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const userStakeAccount = await program.account.userStake.fetch(userStake);
+    assert.isNull(userStakeAccount.delegate);
+  });
+
+  it("33. should close the stake emptied by a position transfer and refund its rent", async () => {
+    const seller = Keypair.generate();
+    const buyer = Keypair.generate();
+    const stakeOf = (owner: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [USER_STAKE_SEED, stakeVault.toBuffer(), owner.toBuffer()],
+        program.programId
+      )[0];
+
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: seller.publicKey,
+          lamports: LAMPORTS_PER_SOL / 10,
+        })
+      )
+    );
+
+    await program.methods
+      .depositStakeFor({ amount: new BN(STAKE_AMOUNT) })
+      .accountsStrict({
+        payer: admin.publicKey,
+        beneficiary: seller.publicKey,
+        payerTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        stakeVault: stakeVault,
+        vaultTokenAccount: vaultTokenAccount,
+        userStake: stakeOf(seller.publicKey),
+        votingPowerRecord: votingPowerPda(stakeOf(seller.publicKey)),
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    await program.methods
+      .transferPosition()
+      .accountsStrict({
+        owner: seller.publicKey,
+        recipient: buyer.publicKey,
+        stakeVault: stakeVault,
+        userStake: stakeOf(seller.publicKey),
+        votingPowerRecord: votingPowerPda(stakeOf(seller.publicKey)),
+        recipientStake: stakeOf(buyer.publicKey),
+        recipientVotingPowerRecord: votingPowerPda(stakeOf(buyer.publicKey)),
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .signers([seller, buyer])
+      .rpc();
+
+    const sellerRecord = votingPowerPda(stakeOf(seller.publicKey));
+    const sellerGeneration = await generationOf(stakeOf(seller.publicKey));
+    const rent =
+      (await provider.connection.getBalance(stakeOf(seller.publicKey))) +
+      (await provider.connection.getBalance(sellerRecord));
+    const sellerBalanceBefore = await provider.connection.getBalance(seller.publicKey);
+
+    await program.methods
+      .closeUserStake()
+      .accountsStrict({
+        owner: seller.publicKey,
+        stakeVault: stakeVault,
+        userStake: stakeOf(seller.publicKey),
+        votingPowerRecord: sellerRecord,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .signers([seller])
+      .rpc();
+
+    const closedAccount = await provider.connection.getAccountInfo(stakeOf(seller.publicKey));
+    assert.isNull(closedAccount);
+    assert.isNull(await provider.connection.getAccountInfo(sellerRecord));
+
+    // The fee is paid by the provider wallet, so the rent comes back in full
+    const sellerBalanceAfter = await provider.connection.getBalance(seller.publicKey);
+    assert.equal(sellerBalanceAfter, sellerBalanceBefore + rent);
+
+    // Created again at the same address, the stake gets a new generation and new request addresses
+    await program.methods
+      .depositStakeFor({ amount: new BN(STAKE_AMOUNT) })
+      .accountsStrict({
+        payer: admin.publicKey,
+        beneficiary: seller.publicKey,
+        payerTokenAccount: adminTokenAccount.address,
+        tokenMint: tokenMint,
+        stakeVault: stakeVault,
+        vaultTokenAccount: vaultTokenAccount,
+        userStake: stakeOf(seller.publicKey),
+        votingPowerRecord: sellerRecord,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const recreatedStake = await program.account.userStake.fetch(stakeOf(seller.publicKey));
+    assert.equal(recreatedStake.unstakeRequestCount.toNumber(), 0);
+    assert.isFalse(recreatedStake.generation.eq(sellerGeneration));
+    assert.isFalse(
+      requestPdaOf(stakeOf(seller.publicKey), recreatedStake.generation, 0).equals(
+        requestPdaOf(stakeOf(seller.publicKey), sellerGeneration, 0)
+      )
+    );
+  });
+
+  it("34. should raise the receipt exchange rate as rewards stream and settle them", async () => {
+    const [receiptMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_mint"), stakeVault.toBuffer()],
+      program.programId
+    );
+    const [stakeRewardTokenAccount] = PublicKey.findProgramAddressSync(
+      [REWARD_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer(), tokenMint.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .depositRewards({
+        amount: new BN(REWARD_AMOUNT),
+        durationSeconds: new BN(TEST_REWARD_DURATION),
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        adminTokenAccount: adminTokenAccount.address,
+        stakeVault: stakeVault,
+        rewardTokenAccount: stakeRewardTokenAccount,
+        rewardMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const vaultBefore = await program.account.stakeVault.fetch(stakeVault);
+    const vaultBalanceBefore = await getAccount(provider.connection, vaultTokenAccount);
+    const supplyBefore = (await getMint(provider.connection, receiptMint)).supply;
+
+    console.log("Waiting 3 seconds for rewards to stream...");
+    await sleep(3000);
+
+    // Receipts priced with rewards still in the reward token account cannot be redeemed
+    const userReceiptTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      receiptMint,
+      user.publicKey
+    );
+    try {
+      await program.methods
+        .unstakeRequest({ amount: new BN(1_000) })
+        .accountsStrict({
+          owner: user.publicKey,
+          stakeVault: stakeVault,
+          userStake: userStake,
+          votingPowerRecord: votingPowerPda(userStake),
+          unstakeRequest: await nextUnstakeRequestPda(),
+          receiptMint: receiptMint,
+          userReceiptTokenAccount: userReceiptTokenAccount.address,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "LiquidRewardsNotSettled");
+    }
+
+    await program.methods
+      .settleLiquidRewards()
+      .accountsStrict({
+        payer: admin.publicKey,
+        stakeVault: stakeVault,
+        tokenMint: tokenMint,
+        rewardTokenAccount: stakeRewardTokenAccount,
+        vaultTokenAccount: vaultTokenAccount,
+        transferAuthority: transferAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    // Rewards compound into the pool while the receipt supply stays put
+    const vaultAfter = await program.account.stakeVault.fetch(stakeVault);
+    const supplyAfter = (await getMint(provider.connection, receiptMint)).supply;
+    const pooledBefore = vaultBefore.liquidPool.pooledAmount.toNumber();
+    const pooledAfter = vaultAfter.liquidPool.pooledAmount.toNumber();
+    assert.equal(supplyAfter, supplyBefore);
+    assert.isAbove(pooledAfter / Number(supplyAfter), pooledBefore / Number(supplyBefore));
+
+    // Everything compounded so far now sits in the vault token account
+    const vaultBalanceAfter = await getAccount(provider.connection, vaultTokenAccount);
+    assert.equal(vaultAfter.liquidPool.uncollectedRewards.toNumber(), 0);
+    assert.equal(
+      Number(vaultBalanceAfter.amount) - Number(vaultBalanceBefore.amount),
+      vaultBefore.liquidPool.uncollectedRewards.toNumber() + pooledAfter - pooledBefore
+    );
+  });
+
+  it("35. should redeem receipts from a wallet without a user stake", async () => {
+    const holder = Keypair.generate();
+    const [receiptMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_mint"), stakeVault.toBuffer()],
+      program.programId
+    );
+    const [holderStake] = PublicKey.findProgramAddressSync(
+      [USER_STAKE_SEED, stakeVault.toBuffer(), holder.publicKey.toBuffer()],
+      program.programId
+    );
+    const holderRequest = requestPdaOf(holderStake, await generationOf(holderStake), 0);
+
+    // The holder pays rent for its user stake and unstake request
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: holder.publicKey,
+          lamports: LAMPORTS_PER_SOL / 10,
+        })
+      )
+    );
+
+    const userReceiptTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      receiptMint,
+      user.publicKey
+    );
+    const holderReceiptTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      receiptMint,
+      holder.publicKey
+    );
+
+    const receipts = Number(userReceiptTokenAccount.amount) / 4;
+    await transfer(
+      provider.connection,
+      (admin as any).payer,
+      userReceiptTokenAccount.address,
+      holderReceiptTokenAccount.address,
+      (admin as any).payer,
+      receipts
+    );
+
+    assert.isNull(await program.account.userStake.fetchNullable(holderStake));
+
+    // Receipts are redeemed once the pool's rewards are in the vault
+    const [stakeRewardTokenAccount] = PublicKey.findProgramAddressSync(
+      [REWARD_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer(), tokenMint.toBuffer()],
+      program.programId
+    );
+    console.log("Waiting 3 seconds for the reward stream to end...");
+    await sleep(3000);
+
+    const settleLiquidRewards = () =>
+      program.methods.settleLiquidRewards().accountsStrict({
+        payer: admin.publicKey,
+        stakeVault: stakeVault,
+        tokenMint: tokenMint,
+        rewardTokenAccount: stakeRewardTokenAccount,
+        vaultTokenAccount: vaultTokenAccount,
+        transferAuthority: transferAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      });
+
+    await settleLiquidRewards().rpc();
+
+    // Settling ahead of the redemption in the same transaction works with nothing left to settle
+    await program.methods
+      .unstakeRequest({ amount: new BN(receipts) })
+      .accountsStrict({
+        owner: holder.publicKey,
+        stakeVault: stakeVault,
+        userStake: holderStake,
+        votingPowerRecord: votingPowerPda(holderStake),
+        unstakeRequest: holderRequest,
+        receiptMint: receiptMint,
+        userReceiptTokenAccount: holderReceiptTokenAccount.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .preInstructions([await settleLiquidRewards().instruction()])
+      .signers([holder])
+      .rpc();
+
+    // The user stake is created on the way, the receipts are worth more than they cost
+    const holderState = await program.account.userStake.fetch(holderStake);
+    assert.isTrue(holderState.owner.equals(holder.publicKey));
+
+    const request = await program.account.unstakeRequest.fetch(holderRequest);
+    assert.isAbove(request.totalAmount.toNumber(), receipts);
+    assert.equal(holderState.unstakingAmount.toNumber(), request.totalAmount.toNumber());
+  });
+
+  it("36. should redeem receipts directly in emergency mode", async () => {
+    const [receiptMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_mint"), stakeVault.toBuffer()],
+      program.programId
+    );
+    const [stakeRewardTokenAccount] = PublicKey.findProgramAddressSync(
+      [REWARD_TOKEN_ACCOUNT_SEED, stakeVault.toBuffer(), tokenMint.toBuffer()],
+      program.programId
+    );
+    const userReceiptTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      (admin as any).payer,
+      receiptMint,
+      user.publicKey
+    );
+
+    // Leave rewards compounded into the pool but not yet settled when the emergency starts
+    await program.methods
+      .depositRewards({
+        amount: new BN(REWARD_AMOUNT),
+        durationSeconds: new BN(TEST_REWARD_DURATION),
+      })
+      .accountsStrict({
+        admin: admin.publicKey,
+        adminTokenAccount: adminTokenAccount.address,
+        stakeVault: stakeVault,
+        rewardTokenAccount: stakeRewardTokenAccount,
+        rewardMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    await sleep(2000);
+
+    await program.methods
+      .pauseVault()
+      .accountsStrict({ admin: admin.publicKey, stakeVault: stakeVault })
+      .rpc();
+    await program.methods
+      .enableEmergencyMode()
+      .accountsStrict({ admin: admin.publicKey, stakeVault: stakeVault })
+      .rpc();
+
+    const emergencyRedeem = () =>
+      program.methods
+        .emergencyRedeem({ receiptAmount: new BN(userReceiptTokenAccount.amount.toString()) })
+        .accountsStrict({
+          owner: user.publicKey,
+          stakeVault: stakeVault,
+          receiptMint: receiptMint,
+          userReceiptTokenAccount: userReceiptTokenAccount.address,
+          userTokenAccount: adminTokenAccount.address,
+          tokenMint: tokenMint,
+          vaultTokenAccount: vaultTokenAccount,
+          transferAuthority: transferAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          eventAuthority: eventAuthority,
+          program: program.programId,
+        })
+        .rpc();
+
+    try {
+      await emergencyRedeem();
+      assert.fail("Should have thrown an error");
+    } catch (error: any) {
+      assert.include(error.message, "LiquidRewardsNotSettled");
+    }
+
+    await program.methods
+      .settleLiquidRewards()
+      .accountsStrict({
+        payer: admin.publicKey,
+        stakeVault: stakeVault,
+        tokenMint: tokenMint,
+        rewardTokenAccount: stakeRewardTokenAccount,
+        vaultTokenAccount: vaultTokenAccount,
+        transferAuthority: transferAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        eventAuthority: eventAuthority,
+        program: program.programId,
+      })
+      .rpc();
+
+    const vaultBefore = await program.account.stakeVault.fetch(stakeVault);
+    const balanceBefore = await getAccount(provider.connection, adminTokenAccount.address);
+
+    await emergencyRedeem();
+
+    // The user holds every receipt left, so the whole pool is paid out
+    const balanceAfter = await getAccount(provider.connection, adminTokenAccount.address);
+    assert.equal(
+      Number(balanceAfter.amount) - Number(balanceBefore.amount),
+      vaultBefore.liquidPool.pooledAmount.toNumber()
+    );
+
+    const vaultAfter = await program.account.stakeVault.fetch(stakeVault);
+    assert.equal(vaultAfter.liquidPool.pooledAmount.toNumber(), 0);
+    assert.equal(
+      vaultAfter.stakeStats.totalStaked.toNumber(),
+      vaultBefore.stakeStats.totalStaked.toNumber() - vaultBefore.liquidPool.pooledAmount.toNumber()
+    );
+
+    const receiptsAfter = await getAccount(provider.connection, userReceiptTokenAccount.address);
+    assert.equal(Number(receiptsAfter.amount), 0);
+  });
 });